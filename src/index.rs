@@ -1,8 +1,9 @@
 use clap::Subcommand;
-use semver::VersionReq;
+use semver::{Version, VersionReq};
 use zip::ZipArchive;
 use crate::config::Config;
 use crate::file::copy_dir_recursive;
+use crate::util::jsonc;
 use crate::util::logging::ask_value;
 use crate::util::mod_file::{parse_mod_info, try_parse_mod_info};
 use crate::{done, info, warn, fatal, NiceUnwrap};
@@ -56,6 +57,13 @@ pub struct Entry {
 	featured: bool,
 }
 
+impl Entry {
+	/// SHA3-256 of the mod's package
+	pub fn hash(&self) -> &str {
+		&self.r#mod.hash
+	}
+}
+
 pub fn update_index(config: &Config) {
 	let index_dir = config.get_current_profile().index_dir();
 	
@@ -166,6 +174,17 @@ pub fn get_entry(config: &Config, id: &String, version: &VersionReq) -> Option<E
 	None
 }
 
+/// Newest version of a mod in the index, if it's there at all
+pub fn get_newest_version(config: &Config, id: &String) -> Option<Version> {
+	index_mods_dir(config)
+		.read_dir()
+		.nice_unwrap("Unable to read index")
+		.filter_map(|dir| try_parse_mod_info(&dir.unwrap().path()).ok())
+		.filter(|mod_info| &mod_info.id == id)
+		.map(|mod_info| mod_info.version)
+		.max()
+}
+
 pub fn install_mod(config: &Config, id: &String, version: &VersionReq) -> PathBuf {
 	let entry = get_entry(config, &id, &version)
 		.nice_unwrap(&format!("Unable to find '{id}' version '{version}'"));
@@ -282,7 +301,12 @@ fn create_entry(out_path: &Path) {
 	}
 
 	create_index_json(&entry_path);
-	fs::copy(&mod_json_path, entry_path.join("mod.json")).nice_unwrap("Unable to copy mod.json");
+	let mod_json = fs::read_to_string(&mod_json_path).nice_unwrap("Unable to read mod.json");
+	fs::write(
+		entry_path.join("mod.json"),
+		jsonc::to_strict_json(&mod_json).nice_unwrap("Unable to parse mod.json"),
+	)
+	.nice_unwrap("Unable to copy mod.json");
 
 	if about_path.exists() {
 		fs::copy(&about_path, entry_path.join("about.md")).nice_unwrap("Unable to copy about.md");
@@ -1,5 +1,6 @@
 use crate::config::{_root};
 use crate::util::logging::ask_value;
+use crate::util::version;
 use std::fs;
 use std::path::PathBuf;
 use git2::{Repository, ResetType, IndexAddOption, Signature};
@@ -114,17 +115,15 @@ pub fn add_mod(package: PathBuf) {
 	
 	let mod_json = mod_json_from_archive(&mut archive);
 
-	let major_version = mod_json
-		.get("version")
-		.nice_unwrap("[mod.json]: Missing key 'version'")
-		.as_str()
-		.nice_unwrap("[mod.json].version: Expected string")
-		.split(".")
-		.next()
-		.unwrap()
-		.chars()
-		.filter(|x| *x != 'v')
-		.collect::<String>();
+	let major_version = version::parse_version(
+		mod_json
+			.get("version")
+			.nice_unwrap("[mod.json]: Missing key 'version'")
+			.as_str()
+			.nice_unwrap("[mod.json].version: Expected string"),
+	)
+	.nice_unwrap("[mod.json].version")
+	.major;
 
 	let mod_id = mod_json_from_archive(&mut archive)
 		.get("id")
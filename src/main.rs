@@ -12,6 +12,8 @@ mod index;
 mod file;
 mod indexer;
 mod project;
+mod sheet;
+mod texturepack;
 
 use util::*;
 
@@ -61,6 +63,18 @@ enum SapphireCommands {
 		commands: crate::package::Package,
 	},
 
+	/// Tools for working with cocos2d spritesheets
+	Sheet {
+		#[clap(subcommand)]
+		commands: crate::sheet::Sheet,
+	},
+
+	/// Tools for building Geometry Dash texture packs
+	Texturepack {
+		#[clap(subcommand)]
+		commands: crate::texturepack::TexturePack,
+	},
+
 	/// Tools for interacting with the Sapphire mod index
 	Index {
 		#[clap(subcommand)]
@@ -93,6 +107,8 @@ fn main() {
 		SapphireCommands::Sdk { commands } => sdk::subcommand(&mut config, commands),
 		SapphireCommands::Package { commands } => package::subcommand(&mut config, commands),
 		SapphireCommands::Project { commands } => project::subcommand(&mut config, commands),
+		SapphireCommands::Sheet { commands } => sheet::subcommand(commands),
+		SapphireCommands::Texturepack { commands } => texturepack::subcommand(&mut config, commands),
 		SapphireCommands::Index { commands } => index::subcommand(&mut config, commands),
 		SapphireCommands::Run { background } => profile::run_profile(&config, None, background)
 	}
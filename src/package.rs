@@ -8,12 +8,20 @@ use zip::write::FileOptions;
 use zip::ZipWriter;
 
 use crate::config::Config;
+use crate::util::audio;
 use crate::util::bmfont;
+use crate::util::depfile;
+use crate::util::jsonc;
+use crate::util::lint;
+use crate::util::localization;
+use crate::util::report;
+use crate::util::shader;
 use crate::util::cache::CacheBundle;
-use crate::util::mod_file::{ModFileInfo, parse_mod_info};
+use crate::util::mod_file::{BitmapFont, ModResources, parse_mod_info, try_parse_mod_info, validate_settings};
+use crate::util::optimize::write_png;
 use crate::util::spritesheet;
 use crate::{cache, project};
-use crate::{done, info, warn, fatal, NiceUnwrap};
+use crate::{done, info, warn, fail, fatal, NiceUnwrap};
 
 #[derive(Subcommand, Debug)]
 #[clap(rename_all = "kebab-case")]
@@ -45,6 +53,12 @@ pub enum Package {
 		install: bool,
 	},
 
+	/// Show the metadata, dependencies and settings of a package or mod
+	Inspect {
+		/// Location of the . package or the mod's folder
+		path: PathBuf,
+	},
+
 	/// Merge multiple packages
 	Merge {
 		/// Packages to merge
@@ -81,6 +95,21 @@ pub enum Package {
 		/// Less verbose output
 		#[clap(long)]
 		shut_up: bool,
+
+		/// Also write report.html to the output folder, previewing the
+		/// created spritesheets and fonts
+		#[clap(long)]
+		report: bool,
+
+		/// Write a Makefile-style depfile listing every input and output,
+		/// for build systems to know when to rerun
+		#[clap(long)]
+		depfile: Option<PathBuf>,
+
+		/// Target to list in the depfile instead of every created file, like
+		/// the output the build system expects the depfile to mention
+		#[clap(long, requires = "depfile")]
+		depfile_target: Option<PathBuf>,
 	},
 }
 
@@ -99,7 +128,7 @@ pub fn install(config: &mut Config, pkg_path: &Path) {
 	);
 }
 
-fn zip_folder(path: &Path, output: &Path) {
+pub fn zip_folder(path: &Path, output: &Path) {
 	info!("Zipping");
 
 	// Setup zip
@@ -148,58 +177,128 @@ pub fn get_working_dir(id: &String) -> PathBuf {
 	working_dir
 }
 
-fn create_resources(
+/// Where resources are built to, and what they're cached with
+pub struct ResourceTarget<'a> {
+	/// Prefix for sprite names (usually the mod ID), if any
+	pub namespace: Option<&'a str>,
+	/// Output of the previous build, to reuse unchanged resources from
+	pub cache_bundle: &'a mut Option<CacheBundle>,
+	/// Cache of this build, which is filled in as resources are built
+	pub cache: &'a mut cache::ResourceCache,
+	/// Directory that cached file names are relative to
+	pub working_dir: &'a Path,
+	pub output_dir: &'a PathBuf,
+}
+
+/// Build resources into the output directory. Sprite names are prefixed with
+/// the namespace if one is given
+pub fn create_resources(
 	#[allow(unused)]
 	config: &mut Config,
-	mod_info: &ModFileInfo,
-	#[allow(unused_mut)]
-	mut cache_bundle: &mut Option<CacheBundle>,
-	cache: &mut cache::ResourceCache,
-	working_dir: &Path,
-	output_dir: &PathBuf,
+	resources: &ModResources,
+	target: ResourceTarget,
 	shut_up: bool,
 ) {
+	let ResourceTarget { namespace, cache_bundle, cache, working_dir, output_dir } = target;
+
 	// Make sure output directory exists
 	fs::create_dir_all(output_dir).nice_unwrap("Could not create resource directory");
 
+	let mut bytes_saved = 0;
+
 	// Create spritesheets
-	for sheet in mod_info.resources.spritesheets.values() {
+	for sheet in resources.spritesheets.values() {
 		let sheet_file = spritesheet::get_spritesheet_bundles(
 			sheet,
 			output_dir,
 			cache_bundle,
-			mod_info,
+			namespace,
+			&resources.texture_format,
+			resources.animation_descriptors,
 			shut_up,
 		);
-		cache.add_sheet(sheet, sheet_file.cache_name(working_dir));
+		cache.add_sheet(
+			sheet,
+			&resources.texture_format,
+			resources.animation_descriptors,
+			sheet_file.cache_name(working_dir),
+		);
+		bytes_saved += sheet_file.bytes_saved;
 	}
 
+	// Read translations first, as fonts may need their characters
+	let languages = (!resources.localization.languages.is_empty())
+		.then(|| localization::read_languages(&resources.localization));
+
 	// Create fonts
-	for font in mod_info.resources.fonts.values() {
-		let font_file = bmfont::get_font_bundles(font, output_dir, cache_bundle, mod_info, shut_up);
-		cache.add_font(font, font_file.cache_name(working_dir));
+	for font in resources.fonts.values() {
+		let extended;
+		let font = match &languages {
+			Some(languages) if resources.localization.font_charsets => {
+				let charset = font.charset.as_deref().unwrap_or(bmfont::DEFAULT_CHARSET).to_string();
+				extended = BitmapFont {
+					charset: Some(
+						localization::used_chars(languages)
+							.into_iter()
+							.fold(charset, |acc, c| acc + "," + &(c as u32).to_string())
+					),
+					..font.clone()
+				};
+				&extended
+			}
+			_ => font,
+		};
+
+		let font_file = bmfont::get_font_bundles(
+			font,
+			output_dir,
+			cache_bundle,
+			&resources.texture_format,
+			shut_up,
+		);
+		cache.add_font(font, &resources.texture_format, font_file.cache_name(working_dir));
+		bytes_saved += font_file.bytes_saved;
+	}
+
+	if let Some(languages) = &languages {
+		localization::write_languages(&resources.localization, languages, output_dir, shut_up);
 	}
 
-	if !&mod_info.resources.sprites.is_empty() {
+	if !&resources.sprites.is_empty() {
 		info!("Copying sprites");
 	}
 	// Resize sprites
-	for sprite_path in &mod_info.resources.sprites {
-		let mut sprite = spritesheet::read_to_image(sprite_path);
+	for sprite_file in &resources.sprites {
+		let sprite_path = &sprite_file.path;
 
 		// Sprite base name
 		let base = sprite_path.file_stem().and_then(|x| x.to_str()).unwrap();
 
+		let optimization = resources.texture_format.png_optimization;
+
 		// Collect all errors
-		(|| {
+		bytes_saved += (|| {
+			// SVGs are rasterized separately for each resolution
+			if spritesheet::is_svg(sprite_path) {
+				let mut saved = 0;
+				for (factor, suffix) in [(1, "-uhd"), (2, "-hd"), (4, "")] {
+					let sprite = spritesheet::read_sprite(sprite_file, factor);
+					saved += write_png(&sprite, &output_dir.join(base.to_string() + suffix + ".png"), optimization)?;
+				}
+				return Ok(saved);
+			}
+
+			let mut sprite = spritesheet::read_to_image(sprite_path);
+
 			spritesheet::downscale(&mut sprite, 1);
-			sprite.save(output_dir.join(base.to_string() + "-uhd.png"))?;
+			let mut saved = write_png(&sprite, &output_dir.join(base.to_string() + "-uhd.png"), optimization)?;
 
 			spritesheet::downscale(&mut sprite, 2);
-			sprite.save(output_dir.join(base.to_string() + "-hd.png"))?;
+			saved += write_png(&sprite, &output_dir.join(base.to_string() + "-hd.png"), optimization)?;
 
 			spritesheet::downscale(&mut sprite, 2);
-			sprite.save(output_dir.join(base.to_string() + ".png"))
+			saved += write_png(&sprite, &output_dir.join(base.to_string() + ".png"), optimization)?;
+			Ok::<_, std::io::Error>(saved)
 		})()
 		.nice_unwrap(&format!(
 			"Unable to copy sprite at {}",
@@ -207,23 +306,35 @@ fn create_resources(
 		));
 	}
 
-	if !&mod_info.resources.files.is_empty() {
+	if !&resources.files.is_empty() {
 		info!("Copying files");
 	}
 	// Move other resources
-	for file in &mod_info.resources.files {
+	for file in &resources.files {
 		std::fs::copy(file, output_dir.join(file.file_name().unwrap()))
 			.nice_unwrap(&format!("Unable to copy file at '{}'", file.display()));
 	}
 
-	if !&mod_info.resources.libraries.is_empty() {
+	if !resources.sounds.files.is_empty() {
+		audio::build_sounds(&resources.sounds, output_dir, working_dir, cache_bundle, cache, shut_up);
+	}
+
+	if !resources.shaders.is_empty() {
+		shader::build_shaders(&resources.shaders, resources.minify_shaders, output_dir, shut_up);
+	}
+
+	if !&resources.libraries.is_empty() {
 		info!("Copying libraries");
 	}
 	// Move other resources
-	for file in &mod_info.resources.libraries {
+	for file in &resources.libraries {
 		std::fs::copy(file, working_dir.join(file.file_name().unwrap()))
 			.nice_unwrap(&format!("Unable to copy file at '{}'", file.display()));
 	}
+
+	if bytes_saved > 0 && !shut_up {
+		info!("PNG optimization saved {:.1} KiB", bytes_saved as f64 / 1024.0);
+	}
 }
 
 fn create_package_resources_only(
@@ -231,6 +342,9 @@ fn create_package_resources_only(
 	root_path: &Path,
 	output_dir: &PathBuf,
 	shut_up: bool,
+	report: bool,
+	depfile_path: Option<PathBuf>,
+	depfile_target: Option<PathBuf>,
 ) {
 	// Parse mod.json
 	let mod_info = parse_mod_info(root_path);
@@ -241,17 +355,38 @@ fn create_package_resources_only(
 
 	create_resources(
 		config,
-		&mod_info,
-		&mut cache_bundle,
-		&mut new_cache,
-		output_dir,
-		output_dir,
+		&mod_info.resources,
+		ResourceTarget {
+			namespace: Some(&mod_info.id),
+			cache_bundle: &mut cache_bundle,
+			cache: &mut new_cache,
+			working_dir: output_dir,
+			output_dir,
+		},
 		shut_up,
 	);
 
 	new_cache.save(output_dir);
 
 	done!("Resources created at {}", output_dir.to_str().unwrap());
+
+	if report {
+		let report_path = output_dir.join("report.html");
+		report::write_report(&mod_info.resources, output_dir, &report_path);
+		done!("Report written to {}", report_path.display());
+	}
+
+	if let Some(depfile_path) = depfile_path {
+		let mut inputs = depfile::resource_inputs(&mod_info.resources);
+		inputs.push(root_path.join("mod.json"));
+
+		let targets = match depfile_target {
+			Some(target) => vec![target],
+			None => depfile::resource_outputs(&mod_info.resources, output_dir),
+		};
+
+		depfile::write_depfile(&depfile_path, &inputs, &targets);
+	}
 }
 
 fn create_package(
@@ -282,11 +417,30 @@ fn create_package(
 		fs::remove_file(&output).unwrap();
 	}
 
+	// The loader would reject the mod with invalid settings
+	let setting_issues = validate_settings(&mod_file_info.settings);
+	for (id, issue) in &setting_issues {
+		fail!("Setting '{}': {}", id, issue);
+	}
+	if !setting_issues.is_empty() {
+		fatal!("Found {} problem(s) in settings", setting_issues.len());
+	}
+
+	// Lint problems don't stop the build, but are worth knowing about
+	for issue in lint::lint_resources(&mod_file_info.resources) {
+		warn!("{}", issue);
+	}
+
 	// Setup working directory
 	let working_dir = get_working_dir(&mod_file_info.id);
 
-	// Move mod.json
-	fs::copy(root_path.join("mod.json"), working_dir.join("mod.json")).unwrap();
+	// Move mod.json, without comments as the loader only reads plain JSON
+	let mod_json = fs::read_to_string(root_path.join("mod.json")).nice_unwrap("Unable to read mod.json");
+	fs::write(
+		working_dir.join("mod.json"),
+		jsonc::to_strict_json(&mod_json).nice_unwrap("Unable to parse mod.json"),
+	)
+	.nice_unwrap("Unable to write mod.json");
 
 	// Setup cache
 	let mut cache_bundle = cache::get_cache_bundle(&output);
@@ -295,11 +449,14 @@ fn create_package(
 	// Create resources
 	create_resources(
 		config,
-		&mod_file_info,
-		&mut cache_bundle,
-		&mut new_cache,
-		&working_dir,
-		&working_dir.join("resources").join(&mod_file_info.id),
+		&mod_file_info.resources,
+		ResourceTarget {
+			namespace: Some(&mod_file_info.id),
+			cache_bundle: &mut cache_bundle,
+			cache: &mut new_cache,
+			working_dir: &working_dir,
+			output_dir: &working_dir.join("resources").join(&mod_file_info.id),
+		},
 		false,
 	);
 
@@ -381,7 +538,50 @@ pub fn mod_json_from_archive<R: Seek + Read>(input: &mut zip::ZipArchive<R>) ->
 		 .read_to_string(&mut text)
 		 .nice_unwrap("Unable to read mod.json");
 
-	serde_json::from_str::<serde_json::Value>(&text).nice_unwrap("Unable to parse mod.json")
+	jsonc::from_str::<serde_json::Value>(&text).nice_unwrap("Unable to parse mod.json")
+}
+
+fn inspect_package(path: &Path) {
+	let mod_info = try_parse_mod_info(path).nice_unwrap("Unable to read mod.json");
+
+	info!("{} {} by {}", mod_info.name, mod_info.version, mod_info.developer);
+	info!("ID: {}", mod_info.id);
+	info!("Description: {}", mod_info.description);
+
+	if !mod_info.dependencies.is_empty() {
+		info!("Dependencies:");
+		for dep in &mod_info.dependencies {
+			info!("  {} {} ({})", dep.id, dep.version, dep.importance);
+		}
+	}
+
+	if !mod_info.settings.is_empty() {
+		info!("Settings:");
+		for (id, setting) in &mod_info.settings {
+			let mut details = vec![setting.kind.to_string()];
+			if let Some(default) = &setting.default {
+				details.push(format!("default {default}"));
+			}
+			if setting.min.is_some() || setting.max.is_some() {
+				details.push(format!(
+					"range {} to {}",
+					setting.min.map(|x| x.to_string()).unwrap_or("-inf".into()),
+					setting.max.map(|x| x.to_string()).unwrap_or("inf".into()),
+				));
+			}
+			if let Some(options) = &setting.one_of {
+				details.push(format!("one of {}", options.join(", ")));
+			}
+
+			match &setting.name {
+				Some(name) => info!("  {} \"{}\" ({})", id, name, details.join(", ")),
+				None => info!("  {} ({})", id, details.join(", ")),
+			}
+			for issue in setting.validate() {
+				warn!("    {}", issue);
+			}
+		}
+	}
 }
 
 fn merge_packages(inputs: Vec<PathBuf>) {
@@ -442,6 +642,8 @@ pub fn subcommand(config: &mut Config, cmd: Package) {
 			install,
 		} => create_package(config, &root_path, binaries, output, install),
 
+		Package::Inspect { path } => inspect_package(&path),
+
 		Package::Merge { packages } => {
 			if packages.len() < 2 {
 				fatal!("Merging requires at least two packages");
@@ -454,12 +656,17 @@ pub fn subcommand(config: &mut Config, cmd: Package) {
 			input,
 			output,
 			externals
-		} => project::check_dependencies(config, input, output, externals, false),
+		} => project::check_dependencies(config, input, output, externals, false, false, false),
 
 		Package::Resources {
 			root_path,
 			output,
 			shut_up,
-		} => create_package_resources_only(config, &root_path, &output, shut_up),
+			report,
+			depfile,
+			depfile_target,
+		} => create_package_resources_only(
+			config, &root_path, &output, shut_up, report, depfile, depfile_target
+		),
 	}
 }
@@ -1,10 +1,12 @@
 
-use std::{fs, path::{PathBuf, Path}, collections::HashMap};
+use std::{fs, path::{PathBuf, Path}, collections::{HashMap, HashSet}};
 use clap::Subcommand;
-use semver::{Version, VersionReq};
-use crate::{util::{config::Config, mod_file::{parse_mod_info, ModFileInfo, Dependency, try_parse_mod_info}}, package::get_working_dir, index::{update_index, index_mods_dir, install_mod}, file::read_dir_recursive, template, indexer};
-use crate::{done, warn, info, fail, fatal, NiceUnwrap};
+use semver::Version;
+use crate::{util::{config::Config, jsonc, lint, lockfile::{Lockfile, LockedDependency, LockSource, LOCKFILE_NAME}, migrate, resolver, schema, version, mod_file::{parse_mod_info, ModFileInfo, try_parse_mod_info, validate_settings}}, package::get_working_dir, index::{update_index, index_mods_dir, install_mod, get_entry, get_newest_version}, file::read_dir_recursive, template, indexer};
+use crate::{done, warn, info, fail, fatal, confirm, NiceUnwrap};
+use crate::sdk::get_version;
 use edit_distance::edit_distance;
+use sha3::{Digest, Sha3_256};
 use crate::util::mod_file::DependencyImportance;
 
 #[derive(Subcommand, Debug)]
@@ -19,6 +21,41 @@ pub enum Project {
     /// Clear this project's cached resource files
     ClearCache,
 
+	/// Check this project's sprites for wrong sizes, needless padding and
+	/// conflicting names
+	LintResources,
+
+	/// Check this project's mod.json for mistakes, with the line and column
+	/// of each one
+	Validate,
+
+	/// Update this project's mod.json to target the installed SDK version,
+	/// applying the changes newer loader versions expect
+	Migrate {
+		/// Write the changes without asking
+		#[clap(short, long)]
+		yes: bool,
+	},
+
+	/// Change this project's version in mod.json
+	Version {
+		#[clap(subcommand)]
+		command: VersionCommand,
+	},
+
+	/// Add, remove or change this project's dependencies in mod.json
+	Dependency {
+		#[clap(subcommand)]
+		command: DependencyCommand,
+	},
+
+	/// Print the JSON Schema of mod.json, for editors to autocomplete with
+	Schema {
+		/// File to write the schema to instead of printing it
+		#[clap(short, long)]
+		output: Option<PathBuf>,
+	},
+
 	/// Check & install the dependencies for this project 
 	Check {
 		/// Where to install the dependencies; usually the project's build 
@@ -37,6 +74,17 @@ pub enum Project {
 		/// Disable updating the mods index before checking dependencies
 		#[clap(long)]
 		dont_update_index: bool,
+
+		/// Ignore the versions in mod.lock.json and pick the newest ones
+		/// again, then record those instead. Also accepts packages that
+		/// changed without their version changing
+		#[clap(long)]
+		update: bool,
+
+		/// Fail if mod.lock.json is missing or any dependency would differ
+		/// from it, instead of updating it
+		#[clap(long, conflicts_with = "update")]
+		locked: bool,
 	},
 
     /// Publish this project on the Sapphire mods index
@@ -60,6 +108,58 @@ pub enum Project {
     ListPublished,
 }
 
+#[derive(Subcommand, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub enum VersionCommand {
+	/// Increase the version, resetting the parts after the one increased
+	Bump {
+		/// Part of the version to increase
+		part: version::VersionPart,
+
+		/// Prerelease tag to bump to, like `alpha` or `rc`. Only applies to
+		/// `pre`, which otherwise keeps the current tag or starts at `beta`
+		#[clap(long, value_name = "TAG")]
+		pre: Option<String>,
+	},
+}
+
+#[derive(Subcommand, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub enum DependencyCommand {
+	/// Add a dependency from the mods index
+	Add {
+		/// ID of the mod to depend on
+		id: String,
+
+		/// Version requirement, like `>=v1.2.0`. If not specified, the newest
+		/// version in the index is used
+		version: Option<String>,
+
+		/// How much the mod needs this dependency
+		#[clap(long)]
+		importance: Option<DependencyImportance>,
+	},
+
+	/// Remove a dependency
+	Remove {
+		/// ID of the dependency to remove
+		id: String,
+	},
+
+	/// Change the version requirement or importance of a dependency
+	Set {
+		/// ID of the dependency to change
+		id: String,
+
+		/// New version requirement
+		version: Option<String>,
+
+		/// New importance
+		#[clap(long)]
+		importance: Option<DependencyImportance>,
+	},
+}
+
 fn find_build_directory(root: &Path) -> Option<PathBuf> {
     // this works for 99% of users. 
     // if you want to parse the CMakeLists.txt file to find the true build 
@@ -109,18 +209,281 @@ fn clear_cache(dir: &Path) {
 	done!("Cache for {} cleared", mod_info.id);
 }
 
+fn lint_project_resources(dir: &Path) {
+	let mod_info = parse_mod_info(dir);
+
+	let issues = lint::lint_resources(&mod_info.resources);
+	if issues.is_empty() {
+		done!("No issues found in resources");
+		return;
+	}
+
+	for issue in &issues {
+		warn!("{}", issue);
+	}
+	fatal!("Found {} issue(s) in resources", issues.len());
+}
+
+fn validate_project(dir: &Path) {
+	let data = fs::read_to_string(dir.join("mod.json")).nice_unwrap("Unable to read mod.json");
+
+	let mut diagnostics = schema::validate_mod_json(&data, dir);
+
+	// The schema can't express everything, so make sure it actually parses
+	if !diagnostics.iter().any(|x| x.severity == schema::Severity::Error) {
+		match try_parse_mod_info(dir) {
+			Ok(mod_info) => {
+				for (id, issue) in validate_settings(&mod_info.settings) {
+					let (line, column) = schema::position_of(&data, &["settings", &id]).unwrap_or((1, 1));
+					diagnostics.push(schema::Diagnostic {
+						line,
+						column,
+						severity: schema::Severity::Error,
+						message: format!("settings.{id}: {issue}"),
+						suggestion: None,
+					});
+				}
+			}
+			Err(e) => {
+				let (line, column) = e.position.unwrap_or((1, 1));
+				diagnostics.push(schema::Diagnostic {
+					line,
+					column,
+					severity: schema::Severity::Error,
+					message: e.message,
+					suggestion: None,
+				});
+			}
+		}
+	}
+
+	diagnostics.sort_by_key(|x| (x.line, x.column));
+
+	let errors = diagnostics.iter().filter(|x| x.severity == schema::Severity::Error).count();
+	for diagnostic in &diagnostics {
+		match diagnostic.severity {
+			schema::Severity::Error => fail!("{}", diagnostic),
+			schema::Severity::Warning => warn!("{}", diagnostic),
+		}
+		if let Some(suggestion) = &diagnostic.suggestion {
+			info!("  {}", suggestion);
+		}
+	}
+
+	if errors > 0 {
+		fatal!("Found {} error(s) in mod.json", errors);
+	}
+	done!("mod.json is valid");
+}
+
+fn print_diff(old: &str, new: &str) {
+	use colored::Colorize;
+
+	// Only show unchanged lines close to a change
+	const CONTEXT: usize = 2;
+
+	let lines = migrate::diff_lines(old, new);
+	let changed = lines
+		.iter()
+		.enumerate()
+		.filter(|(_, x)| !matches!(x, migrate::DiffLine::Same(_)))
+		.map(|(i, _)| i)
+		.collect::<Vec<_>>();
+
+	let mut skipped = false;
+	for (i, line) in lines.iter().enumerate() {
+		match line {
+			migrate::DiffLine::Removed(x) => println!("{}", format!("- {x}").bright_red()),
+			migrate::DiffLine::Added(x) => println!("{}", format!("+ {x}").bright_green()),
+			migrate::DiffLine::Same(x) => {
+				if changed.iter().any(|c| c.abs_diff(i) <= CONTEXT) {
+					println!("  {x}");
+					skipped = false;
+				} else if !skipped {
+					println!("{}", "  ...".dimmed());
+					skipped = true;
+				}
+			}
+		}
+	}
+}
+
+fn migrate_project(dir: &Path, yes: bool) {
+	let path = dir.join("mod.json");
+	let text = fs::read_to_string(&path).nice_unwrap("Unable to read mod.json");
+
+	let current = jsonc::from_str::<serde_json::Value>(&text)
+		.nice_unwrap("Unable to parse mod.json")
+		.get("")
+		.and_then(|x| x.as_str().map(|x| x.to_string()))
+		.nice_unwrap("[mod.json]: Missing key ''");
+	let current = version::parse_version(&current).nice_unwrap("[mod.json].");
+	let target = get_version();
+
+	if current >= target {
+		done!("mod.json already targets v{}", current);
+		return;
+	}
+
+	let (new_text, applied) = migrate::migrate(&text, &current, &target).nice_unwrap("Unable to migrate mod.json");
+
+	info!("Migrating mod.json from v{} to v{}", current, target);
+	if applied.is_empty() {
+		info!("  No changes to mod.json between these versions, only its target version is updated");
+	}
+	for description in applied {
+		info!("  {}", description);
+	}
+	print_diff(&text, &new_text);
+
+	if !yes && !confirm!("Write the changes to mod.json?") {
+		fatal!("Aborting");
+	}
+
+	fs::write(&path, new_text).nice_unwrap("Unable to write mod.json");
+	done!("Migrated mod.json to v{}", target);
+}
+
+fn read_mod_json(dir: &Path) -> jsonc::JsonEditor {
+	let text = fs::read_to_string(dir.join("mod.json")).nice_unwrap("Unable to read mod.json");
+	jsonc::JsonEditor::new(text).nice_unwrap("Unable to parse mod.json")
+}
+
+fn write_mod_json(dir: &Path, editor: jsonc::JsonEditor) {
+	fs::write(dir.join("mod.json"), editor.text()).nice_unwrap("Unable to write mod.json");
+}
+
+fn bump_version(dir: &Path, part: version::VersionPart, tag: Option<String>) {
+	if tag.is_some() && !matches!(part, version::VersionPart::Pre) {
+		fatal!("--pre only applies when bumping 'pre'");
+	}
+
+	let mut editor = read_mod_json(dir);
+
+	let text = editor
+		.get(&["version"])
+		.and_then(|x| x.as_str().map(|x| x.to_string()))
+		.nice_unwrap("[mod.json]: Missing key 'version'");
+	let current = version::parse_version(&text).nice_unwrap("[mod.json].version");
+	let new = version::bump(&current, part, tag.as_deref()).nice_unwrap("Unable to bump version");
+
+	// Keep the prefix the version was written with
+	let new_text = if text.starts_with(['v', 'V']) { format!("v{new}") } else { new.to_string() };
+	editor.set(&["version"], &serde_json::json!(new_text)).nice_unwrap("Unable to edit mod.json");
+	write_mod_json(dir, editor);
+
+	done!("Bumped version from {} to {}", text, new_text);
+}
+
+/// Index of a dependency in mod.json's dependencies array
+fn dependency_index(editor: &jsonc::JsonEditor, id: &str) -> Option<usize> {
+	editor
+		.get(&["dependencies"])?
+		.as_array()?
+		.iter()
+		.position(|x| x.get("id").and_then(|x| x.as_str()) == Some(id))
+}
+
+/// Check that a version requirement parses and that the index has a
+/// version of the mod matching it
+fn check_indexed_dependency(config: &Config, id: &String, req: &str) {
+	let parsed = version::parse_version_req(req).nice_unwrap("Invalid version requirement");
+	if get_entry(config, id, &parsed).is_none() {
+		fatal!("Unable to find '{}' version '{}' in the index", id, req);
+	}
+}
+
+fn dependency_subcommand(config: &Config, dir: &Path, cmd: DependencyCommand) {
+	let mut editor = read_mod_json(dir);
+
+	match cmd {
+		DependencyCommand::Add { id, version, importance } => {
+			if dependency_index(&editor, &id).is_some() {
+				fatal!("'{}' is already a dependency; use `project dependency set` to change it", id);
+			}
+
+			let version = match version {
+				Some(version) => {
+					check_indexed_dependency(config, &id, &version);
+					version
+				}
+				None => format!(
+					"v{}",
+					get_newest_version(config, &id).nice_unwrap(format!("Unable to find '{id}' in the index"))
+				),
+			};
+
+			let mut dep = serde_json::Map::new();
+			dep.insert("id".into(), id.clone().into());
+			dep.insert("version".into(), version.clone().into());
+			if let Some(importance) = importance {
+				dep.insert("importance".into(), importance.to_string().into());
+			}
+			editor
+				.push(&["dependencies"], &serde_json::Value::Object(dep))
+				.nice_unwrap("Unable to edit mod.json");
+			write_mod_json(dir, editor);
+
+			done!("Added dependency '{}' {}", id, version);
+		}
+
+		DependencyCommand::Remove { id } => {
+			let index = dependency_index(&editor, &id)
+				.nice_unwrap(format!("'{id}' is not a dependency"))
+				.to_string();
+			editor.remove(&["dependencies", &index]);
+			write_mod_json(dir, editor);
+
+			done!("Removed dependency '{}'", id);
+		}
+
+		DependencyCommand::Set { id, version, importance } => {
+			if version.is_none() && importance.is_none() {
+				fatal!("Nothing to change; specify a version or --importance");
+			}
+			let index = dependency_index(&editor, &id)
+				.nice_unwrap(format!("'{id}' is not a dependency"))
+				.to_string();
+
+			if let Some(version) = &version {
+				check_indexed_dependency(config, &id, version);
+				editor
+					.set(&["dependencies", &index, "version"], &serde_json::json!(version))
+					.nice_unwrap("Unable to edit mod.json");
+			}
+			if let Some(importance) = importance {
+				// 'importance' replaces the old 'required'
+				editor.remove(&["dependencies", &index, "required"]);
+				editor
+					.set(&["dependencies", &index, "importance"], &serde_json::json!(importance.to_string()))
+					.nice_unwrap("Unable to edit mod.json");
+			}
+			write_mod_json(dir, editor);
+
+			done!("Updated dependency '{}'", id);
+		}
+	}
+}
+
+fn print_schema(output: Option<PathBuf>) {
+	let schema = serde_json::to_string_pretty(&schema::mod_json_schema()).unwrap();
+	match output {
+		Some(output) => {
+			fs::write(&output, schema).nice_unwrap("Unable to write schema");
+			done!("Schema written to {}", output.display());
+		}
+		None => println!("{}", schema),
+	}
+}
+
 #[derive(PartialEq)]
 enum Found {
-	/// No matching dependency found
+	/// Nothing similar found
 	None,
-	/// No matching dependency found, but one with a similar ID was found
+	/// A mod with a similar ID was found
 	Maybe(String),
-	/// Dependency found, but it was not an API
+	/// A mod with the ID was found, but it was not an API
 	NotAnApi,
-	/// Dependency found, but it was the wrong version
-	Wrong(Version),
-	/// Dependency found
-	Some(PathBuf, ModFileInfo),
 }
 
 impl Found {
@@ -129,8 +492,6 @@ impl Found {
 			Found::None         => 0,
 			Found::Maybe(_)     => 1,
 			Found::NotAnApi     => 2,
-			Found::Wrong(_)     => 3,
-			Found::Some(_, _)   => 4,
 		}
 	}
 
@@ -149,8 +510,9 @@ impl Found {
 	}
 }
 
+/// Look for what the user may have meant by a dependency that wasn't found
 fn find_dependency(
-	dep: &Dependency,
+	id: &str,
 	dir: &PathBuf,
 	search_recursive: bool
 ) -> Result<Found, std::io::Error> {
@@ -167,23 +529,14 @@ fn find_dependency(
 			continue;
 		};
 		// check if the id matches
-		if dep.id == info.id {
-			if info.api.is_some() {
-				if dep.version.matches(&info.version) {
-					found.promote(Found::Some(dir, info));
-					break;
-				}
-				else {
-					found.promote(Found::Wrong(info.version));
-				}
-			}
-			else {
+		if id == info.id {
+			if info.api.is_none() {
 				found.promote(Found::NotAnApi);
 			}
 		}
 		// otherwise check if maybe the id was misspelled
 		else {
-			let dist = edit_distance(&dep.id, &info.id);
+			let dist = edit_distance(id, &info.id);
 			if dist < closest_score {
 				found.promote_eq(Found::Maybe(info.id.clone()));
 				closest_score = dist;
@@ -193,17 +546,294 @@ fn find_dependency(
 	Ok(found)
 }
 
+/// Where a version of a dependency comes from
+enum Source {
+	Installed(PathBuf),
+	Index,
+	External,
+}
+
+/// Every version of every API mod that dependencies can be picked from.
+/// Installed mods come before the index so they're preferred, and newer
+/// versions come before older ones
+struct Catalog {
+	candidates: HashMap<String, Vec<resolver::Candidate<Source>>>,
+	/// Versions that are on the index, even if an installed copy is used
+	indexed: HashSet<(String, Version)>,
+}
+
+impl Catalog {
+	fn load(config: &Config, externals: &HashMap<String, Option<Version>>) -> Catalog {
+		let mut candidates = HashMap::<String, Vec<resolver::Candidate<Source>>>::new();
+		let mut indexed = HashSet::new();
+
+		// Only dependencies that are required have to be there to build
+		let candidate = |info: ModFileInfo, source| resolver::Candidate {
+			version: Some(info.version),
+			dependencies: info.dependencies
+				.into_iter()
+				.filter(|x| x.is_required())
+				.map(|x| (x.id, x.version))
+				.collect(),
+			source,
+		};
+
+		let installed = read_dir_recursive(&config.get_current_profile().mods_dir())
+			.nice_unwrap("Unable to read installed mods");
+		let mut from_index = Vec::new();
+		for dir in index_mods_dir(config).read_dir().nice_unwrap("Unable to read index") {
+			let Ok(info) = try_parse_mod_info(&dir.unwrap().path()) else {
+				continue;
+			};
+			if info.api.is_some() {
+				indexed.insert((info.id.clone(), info.version.clone()));
+				from_index.push(info);
+			}
+		}
+
+		let mut from_installed = Vec::new();
+		for path in installed {
+			if let Ok(info) = try_parse_mod_info(&path) {
+				if info.api.is_some() {
+					from_installed.push((info, path));
+				}
+			}
+		}
+		from_installed.sort_by(|a, b| b.0.version.cmp(&a.0.version));
+		from_index.sort_by(|a, b| b.version.cmp(&a.version));
+
+		for (info, path) in from_installed {
+			candidates.entry(info.id.clone()).or_default().push(candidate(info, Source::Installed(path)));
+		}
+		for info in from_index {
+			let versions = candidates.entry(info.id.clone()).or_default();
+			// No need to download what's already installed
+			if !versions.iter().any(|x| x.version.as_ref() == Some(&info.version)) {
+				versions.push(candidate(info, Source::Index));
+			}
+		}
+
+		// Externals are used as-is, whatever else there is
+		for (id, version) in externals {
+			candidates.insert(id.clone(), vec![resolver::Candidate {
+				version: version.clone(),
+				dependencies: Vec::new(),
+				source: Source::External,
+			}]);
+		}
+
+		Catalog { candidates, indexed }
+	}
+
+	/// Move the locked version of each dependency to the front, so it's
+	/// picked again whenever it still satisfies the requirements
+	fn prefer_locked(&mut self, lockfile: &Lockfile) {
+		for (id, locked) in &lockfile.dependencies {
+			let Some(version) = locked.version.as_ref().and_then(|x| version::parse_version(x).ok()) else {
+				continue;
+			};
+			if let Some(versions) = self.candidates.get_mut(id) {
+				versions.sort_by_key(|x| x.version.as_ref() != Some(&version));
+			}
+		}
+	}
+
+	fn installed_versions(&self, id: &str) -> impl Iterator<Item = &Version> {
+		self.candidates
+			.get(id)
+			.into_iter()
+			.flatten()
+			.filter(|x| matches!(x.source, Source::Installed(_)))
+			.filter_map(|x| x.version.as_ref())
+	}
+}
+
+/// SHA3-256 of a package, as recorded in the index and lockfile
+fn hash_file(path: &Path) -> String {
+	let mut hasher = Sha3_256::new();
+	hasher.update(fs::read(path).nice_unwrap("Unable to read package"));
+	hex::encode(hasher.finalize())
+}
+
+/// Hash of the package extracted into a dependency directory, if any
+fn extracted_hash(options: &Path) -> Option<String> {
+	let options = fs::read_to_string(options).ok()?;
+	serde_json::from_str::<serde_json::Value>(&options)
+		.ok()?
+		.get("hash")?
+		.as_str()
+		.map(|x| x.to_string())
+}
+
+/// Remove extracted dependencies that aren't used anymore, so CMake doesn't
+/// pick them up
+fn remove_stale_dependencies(dep_dir: &Path, keep: &HashSet<String>) {
+	let Ok(dirs) = dep_dir.read_dir() else {
+		return;
+	};
+	for dir in dirs {
+		let path = dir.unwrap().path();
+		let id = path.file_name().unwrap().to_string_lossy().to_string();
+		// only touch directories that were extracted here
+		if path.join("-dep-options.json").exists() && !keep.contains(&id) {
+			info!("Removing unused dependency '{}'", id);
+			fs::remove_dir_all(&path).nice_unwrap("Unable to remove unused dependency");
+		}
+	}
+}
+
+/// Compare a new lockfile against what was locked before, failing if it
+/// changed while `--locked` was given, or if a package changed without its
+/// version changing unless `--update` was. Returns the changes
+fn compare_lockfile(
+	lockfile: &mut Lockfile,
+	old_lockfile: Option<Lockfile>,
+	locked: bool,
+	update: bool,
+) -> Vec<String> {
+	let old_lockfile = old_lockfile.unwrap_or_default();
+	lockfile.keep_sources(&old_lockfile);
+
+	let mismatches = lockfile.hash_mismatches(&old_lockfile);
+	if !update && !mismatches.is_empty() {
+		for id in mismatches {
+			fail!(
+				"Package of '{}' {} doesn't match the hash in {}",
+				id, lockfile.dependencies[&id].version.as_deref().unwrap_or("external"), LOCKFILE_NAME
+			);
+		}
+		fatal!(
+			"Dependencies changed without a new version; run `project check --update` \
+			if the new packages are trusted"
+		);
+	}
+
+	let changes = lockfile.changes_from(&old_lockfile);
+	if locked && !changes.is_empty() {
+		for change in changes {
+			fail!("{}", change);
+		}
+		fatal!(
+			"{} is out of date; run `project check --update` to update it",
+			LOCKFILE_NAME
+		);
+	}
+	changes
+}
+
+fn write_lockfile(dir: &Path, lockfile: &Lockfile, changes: Vec<String>) {
+	for change in changes {
+		info!("{}", change);
+	}
+	lockfile.write(dir).nice_unwrap("Unable to write lockfile");
+	info!("Updated {}", LOCKFILE_NAME);
+}
+
+/// Explain why no versions could be picked, with hints about likely mistakes
+fn print_conflict(config: &Config, conflict: &resolver::Conflict, required: bool) {
+	let print = |text: String| if required { fail!("{}", text) } else { info!("{}", text) };
+
+	if conflict.available.is_empty() {
+		print(format!(
+			"Dependency '{}' not found in installed mods nor index!",
+			conflict.id
+		));
+	}
+	else {
+		print(format!(
+			"No version of dependency '{}' satisfies everything that depends on it",
+			conflict.id
+		));
+	}
+	for (by, version) in &conflict.requirements {
+		info!("  {} requires '{}' version '{}'", by, conflict.id, version);
+	}
+
+	if !conflict.available.is_empty() {
+		let available = conflict.available
+			.iter()
+			.map(|x| x.as_ref().map(|x| format!("v{x}")).unwrap_or("external".into()))
+			.collect::<Vec<_>>();
+		info!("  Available versions: {}", available.join(", "));
+		return;
+	}
+
+	if required {
+		info!(
+			"If this is a mod that hasn't been published yet, install it \
+			locally first, or if it's a closed-source mod that won't be \
+			on the index, mark it as external in your CMake using \
+			setup__mod(... EXTERNALS {}:<version>)",
+			conflict.id
+		);
+	}
+
+	let found_in_index = find_dependency(
+		&conflict.id, &index_mods_dir(config), false
+	).nice_unwrap("Unable to read index");
+	let found_in_installed = find_dependency(
+		&conflict.id, &config.get_current_profile().mods_dir(), true
+	).nice_unwrap("Unable to read installed mods");
+
+	// misspelled message
+	match (&found_in_index, &found_in_installed) {
+		(in_index @ Found::Maybe(m), _) | (in_index @ _, Found::Maybe(m)) => {
+			info!(
+				"Another mod with a similar ID was found in {}: {m} \
+				- maybe you misspelled?",
+				if matches!(in_index, Found::Maybe(_)) {
+					"index"
+				} else {
+					"installed mods"
+				}
+			);
+		},
+		_ => {},
+	}
+	// not-an-api message
+	match (&found_in_index, &found_in_installed) {
+		(in_index @ Found::NotAnApi, _) | (in_index @ _, Found::NotAnApi) => {
+			info!(
+				"A mod with the ID '{}' was found in {}, but it was not marked \
+				as an API - this may be a mistake; if you are the developer \
+				of the dependency, add the \"api\" key to its mod.json",
+				conflict.id,
+				if matches!(in_index, Found::NotAnApi) {
+					"index"
+				} else {
+					"installed mods"
+				}
+			);
+		},
+		_ => {},
+	}
+}
+
 pub fn check_dependencies(
 	config: &Config,
 	input: PathBuf,
 	output: PathBuf,
 	externals: Vec<String>,
-	dont_update_index: bool
+	dont_update_index: bool,
+	update: bool,
+	locked: bool
 ) {
 	let mod_info = parse_mod_info(&input);
+	let old_lockfile = Lockfile::read(&input).nice_unwrap("Unable to read lockfile");
+	if locked && old_lockfile.is_none() {
+		fatal!("No {} found, but --locked was specified", LOCKFILE_NAME);
+	}
+
+	let dep_dir = output.join("-deps");
 
-	// If no dependencies, skippy wippy
+	// If no dependencies, skippy wippy (but still unlock removed ones)
 	if mod_info.dependencies.is_empty() {
+		let mut lockfile = Lockfile::default();
+		let changes = compare_lockfile(&mut lockfile, old_lockfile, locked, update);
+		remove_stale_dependencies(&dep_dir, &HashSet::new());
+		if !changes.is_empty() {
+			write_lockfile(&input, &lockfile, changes);
+		}
 		return;
 	}
 
@@ -217,9 +847,9 @@ pub fn check_dependencies(
 				let mut split = ext.split(":");
 				let name = split.next().unwrap().to_string();
 				let ver = split.next().unwrap();
-				(name, Some(Version::parse(ver.strip_prefix("v").unwrap_or(ver))
-					.nice_unwrap("Invalid version in external {name}")
-				))
+				let ver = version::parse_version(ver)
+					.nice_unwrap(format!("Invalid version in external {name}"));
+				(name, Some(ver))
 			}
 			else {
 				(ext, None)
@@ -237,228 +867,211 @@ pub fn check_dependencies(
 		update_index(config);
 	}
 
-	let dep_dir = output.join("-deps");
 	fs::create_dir_all(&dep_dir).nice_unwrap("Unable to create dependency directory");
 
-	// check all dependencies
-	for dep in mod_info.dependencies {
+	// check external dependencies
+	let mut roots = Vec::new();
+	for dep in &mod_info.dependencies {
 		// is this an external dependency?
-		if let Some(ext) = externals.get(&dep.id) {
-			// did we get a version?
-			if let Some(version) = ext {
-				// is it valid?
-				if dep.version.matches(version) {
-					info!("Dependency '{}' found as external", dep.id);
-				}
-				// external dependency version must match regardless of whether 
-				// it's optional or not as most external dependencies are other 
-				// projects being built at the same time and if those have a 
-				// version mismatch you've screwed something up and should fix 
-				// that
-				else {
-					fail!(
-						"External dependency '{}' version '{version}' does not \
-						match required version '{}' (note: optionality is \
-						ignored when verifying external dependencies)",
-						dep.id, dep.version
-					);
-					errors = true;
-				}
+		let Some(ext) = externals.get(&dep.id) else {
+			roots.push(resolver::Requirement {
+				id: dep.id.clone(),
+				version: dep.version.clone(),
+				by: "mod.json".into(),
+				required: dep.is_required(),
+			});
+			continue;
+		};
+		// did we get a version?
+		if let Some(version) = ext {
+			// is it valid?
+			if dep.version.matches(version) {
+				info!("Dependency '{}' found as external", dep.id);
 			}
-			// otherwise warn that a version prolly should be provided, but let 
-			// it slide this time
+			// external dependency version must match regardless of whether 
+			// it's optional or not as most external dependencies are other 
+			// projects being built at the same time and if those have a 
+			// version mismatch you've screwed something up and should fix 
+			// that
 			else {
-				warn!(
-					"Dependency '{}' marked as external with no version specified",
-					dep.id
+				fail!(
+					"External dependency '{}' version '{version}' does not \
+					match required version '{}' (note: optionality is \
+					ignored when verifying external dependencies)",
+					dep.id, dep.version
 				);
+				errors = true;
 			}
-			continue;
 		}
+		// otherwise warn that a version prolly should be provided, but let 
+		// it slide this time
+		else {
+			warn!(
+				"Dependency '{}' marked as external with no version specified",
+				dep.id
+			);
+		}
+	}
 
-		// otherwise try to find it on installed mods and then on index
+	// pick versions of the dependencies and everything they depend on, 
+	// required ones first so an optional one can never break the build
+	let mut catalog = Catalog::load(config, &externals);
+	if let Some(old_lockfile) = old_lockfile.as_ref().filter(|_| !update) {
+		catalog.prefer_locked(old_lockfile);
+	}
+	let (mut accepted, optional): (Vec<_>, Vec<_>) = roots.into_iter().partition(|x| x.required);
 
-		// check index
-		let found_in_index = find_dependency(
-			&dep, &index_mods_dir(config), false
-		).nice_unwrap("Unable to read index");
+	let mut resolution = match resolver::resolve(&catalog.candidates, &accepted) {
+		Ok(resolution) => resolution,
+		Err(conflict) => {
+			print_conflict(config, &conflict, true);
+			fatal!("Some dependencies were unresolved");
+		}
+	};
+	for dep in optional {
+		accepted.push(dep);
+		match resolver::resolve(&catalog.candidates, &accepted) {
+			Ok(new) => resolution = new,
+			Err(conflict) => {
+				let dep = accepted.pop().unwrap();
+				info!("Skipping optional dependency '{}'", dep.id);
+				print_conflict(config, &conflict, false);
+			}
+		}
+	}
 
-		// check installed mods
-		let found_in_installed = find_dependency(
-			&dep, &config.get_current_profile().mods_dir(), true
-		).nice_unwrap("Unable to read installed mods");
+	// decide where each dependency comes from and record it
+	let mut lockfile = Lockfile::default();
+	let mut to_extract = Vec::new();
+	for (id, selected) in &resolution {
+		let candidate = &catalog.candidates[id][selected.candidate];
+		let locked_version = candidate.version.as_ref().map(|x| format!("v{x}"));
 
-		// if not found in either        hjfod  code
-		if !matches!(found_in_index,     Found::Some(_, _)) &&
-		   !matches!(found_in_installed, Found::Some(_, _))
-		{
-			if dep.importance == DependencyImportance::Required ||
-				dep.required.is_some() && dep.required.unwrap() {
-				fail!(
-					"Dependency '{0}' not found in installed mods nor index! \
-					If this is a mod that hasn't been published yet, install it \
-					locally first, or if it's a closed-source mod that won't be \
-					on the index, mark it as external in your CMake using \
-					setup__mod(... EXTERNALS {0}:{1})",
-					dep.id, dep.version
-				);
-				errors = true;
-			}
-			else {
-				info!(
-					"Dependency '{}' not found in installed mods nor index",
-					dep.id
-				)
-			}
-			// bad version
-			match (&found_in_index, &found_in_installed) {
-				(in_index @ Found::Wrong(ver), _) | (in_index @ _, Found::Wrong(ver)) => {
-					info!(
-						"Version '{ver}' of the mod was found in {}, but it was \
-						rejected because version '{}' is required by the dependency",
-						if matches!(in_index, Found::Wrong(_)) {
-							"index"
-						} else {
-							"installed mods"
-						},
-						dep.version
-					);
-				},
-				_ => {},
+		match &candidate.source {
+			Source::External => {
+				// direct externals were already checked above
+				if !mod_info.dependencies.iter().any(|d| &d.id == id) {
+					info!("Dependency '{}' found as external", id);
+				}
+				lockfile.dependencies.insert(id.clone(), LockedDependency {
+					version: locked_version,
+					source: LockSource::External,
+					hash: None,
+				});
 			}
-			// misspelled message
-			match (&found_in_index, &found_in_installed) {
-				(in_index @ Found::Maybe(m), _) | (in_index @ _, Found::Maybe(m)) => {
-					info!(
-						"Another mod with a similar ID was found in {}: {m} \
-						- maybe you misspelled?",
-						if matches!(in_index, Found::Maybe(_)) {
-							"index"
-						} else {
-							"installed mods"
-						}
+
+			Source::Installed(path) => {
+				let version = candidate.version.clone().unwrap();
+				if catalog.indexed.contains(&(id.clone(), version.clone())) {
+					info!("Dependency '{}' found", id);
+				}
+				else {
+					warn!(
+						"Dependency '{}' found in installed mods, but not on the \
+						mods index - make sure that the mod is published on the \
+						index when you publish yours, as otherwise users won't be \
+						able to install your mod through the index!",
+						id
 					);
-				},
-				_ => {},
-			}
-			// not-an-api message
-			match (&found_in_index, &found_in_installed) {
-				(in_index @ Found::NotAnApi, _) | (in_index @ _, Found::NotAnApi) => {
 					info!(
-						"A mod with the ID '{}' was found in {}, but it was not marked \
-						as an API - this may be a mistake; if you are the developer \
-						of the dependency, add the \"api\" key to its mod.json",
-						dep.id,
-						if matches!(in_index, Found::NotAnApi) {
-							"index"
-						} else {
-							"installed mods"
-						}
+						"If '{0}' is a closed-source mod that won't be released on \
+						the index, mark it as external in your CMake with \
+						setup__mod(... EXTERNALS {0}:{1})",
+						id, version
 					);
-				},
-				_ => {},
-			}
-			// skip rest
-			continue;
-		}
-
-		let path_to_dep_;
-		let __info;
-		match (found_in_installed, found_in_index) {
-			(Found::Some(inst_path, inst_info), Found::Some(_, _)) => {
-				info!("Dependency '{}' found", dep.id);
-				path_to_dep_ = inst_path;
-				__info = inst_info;
-			}
-
-			(Found::Some(inst_path, inst_info), _) => {
-				warn!(
-					"Dependency '{}' found in installed mods, but not on the \
-					mods index - make sure that the mod is published on the \
-					index when you publish yours, as otherwise users won't be \
-					able to install your mod through the index!",
-					dep.id
-				);
-				info!(
-					"If '{0}' is a closed-source mod that won't be released on \
-					the index, mark it as external in your CMake with \
-					setup__mod(... EXTERNALS {0}:{1})",
-					dep.id, dep.version
-				);
-				path_to_dep_ = inst_path;
-				__info = inst_info;
+				}
+				lockfile.dependencies.insert(id.clone(), LockedDependency {
+					version: locked_version,
+					source: LockSource::Installed,
+					hash: Some(hash_file(path)),
+				});
+				to_extract.push((id, selected, Some(path.clone())));
 			}
 
-			(Found::Wrong(version), Found::Some(_, indx_info)) => {
-				if version > indx_info.version {
+			Source::Index => {
+				let version = candidate.version.as_ref().unwrap();
+				if let Some(installed) = catalog.installed_versions(id).max().filter(|x| *x > version) {
 					warn!(
 						"Dependency '{0}' found in installed mods, but as \
-						version '{1}' whereas required is '{2}'. Index has valid \
-						version '{3}', but not using it as it appears you have \
-						a newer version installed. Either manually downgrade \
-						the installed '{0}' to '{3}', or update your mod.json's \
-						dependency requirements",
-						dep.id, version, dep.version, indx_info.version
+						version '{1}' which doesn't satisfy the requirements. \
+						Index has valid version '{2}', but not using it as it \
+						appears you have a newer version installed. Either \
+						manually downgrade the installed '{0}' to '{2}', or \
+						update the dependency requirements",
+						id, installed, version
 					);
+					errors = true;
 					continue;
 				}
-				info!(
-					"Dependency '{}' found on the index, installing \
-					(update '{}' => '{}')",
-					dep.id, version, indx_info.version
-				);
-				path_to_dep_ = install_mod(
-					config, &indx_info.id,
-					&VersionReq::parse(&format!("=={}", indx_info.version.to_string())).unwrap()
-				);
-				__info = indx_info;
+				let entry = get_entry(config, id, &version::exact_req(version))
+					.nice_unwrap(format!("Unable to find '{id}' version '{version}'"));
+				lockfile.dependencies.insert(id.clone(), LockedDependency {
+					version: locked_version,
+					source: LockSource::Index,
+					hash: Some(entry.hash().to_string()),
+				});
+				// installed after checking the lockfile
+				to_extract.push((id, selected, None));
 			}
+		}
+	}
+
+	// compare against what was locked before
+	let changes = compare_lockfile(&mut lockfile, old_lockfile, locked, update);
+
+	for (id, selected, path_to_dep_) in to_extract {
+		let hash = lockfile.dependencies[id].hash.clone().unwrap();
+		let options = dep_dir.join(id).join("-dep-options.json");
+
+		// add a note saying if the dependencey is required or not (for cmake to 
+		// know if to link or not), and which package was extracted
+		let options_json = format!(
+			r#"{{ "required": {}, "hash": "{}" }}"#,
+			selected.required, hash
+		);
+
+		// skip extracting if the same package was already extracted
+		if extracted_hash(&options).as_ref() == Some(&hash) {
+			fs::write(&options, options_json).nice_unwrap("Unable to save dep options");
+			continue;
+		}
 
-			(_, Found::Some(_, indx_info)) => {
-				info!(
+		let path_to_dep_ = path_to_dep_.unwrap_or_else(|| {
+			let version = catalog.candidates[id][selected.candidate].version.as_ref().unwrap();
+			match catalog.installed_versions(id).max() {
+				Some(installed) => info!(
+					"Dependency '{}' found on the index, installing \
+					(update '{}' => '{}')",
+					id, installed, version
+				),
+				None => info!(
 					"Dependency '{}' found on the index, installing (version '{}')",
-					dep.id, indx_info.version
-				);
-				path_to_dep_ = install_mod(
-					config, &indx_info.id,
-					&VersionReq::parse(&format!("={}", indx_info.version.to_string())).unwrap()
-				);
-				__info = indx_info;
+					id, version
+				),
 			}
+			install_mod(config, id, &version::exact_req(version))
+		});
 
-			_ => unreachable!()
+		// clear out files of the previously extracted version
+		if dep_dir.join(id).exists() {
+			fs::remove_dir_all(dep_dir.join(id)).nice_unwrap("Unable to remove old dependency");
 		}
 
-		// check already installed dependencies
-		// let found_in_deps = find_dependency(
-		// 	&dep, &dep_dir, false
-		// ).nice_unwrap("Unable to read dependencies");
-
-		// !this check may be added back at some point, but for now there's not 
-		// too much performance benefit from doing this, and doing it might 
-		// cause issues if the dependency has changes
-		// check if dependency already installed
-		// if let Found::Some(_, info) = found_in_deps {
-		// 	if info.version == _info.version {
-		// 		continue;
-		// 	}
-		// }
-
 		// unzip the whole . package because there's only like a few 
 		// extra files there aside from the lib, headers, and resources
 		zip::ZipArchive::new(fs::File::open(path_to_dep_).unwrap())
 			.nice_unwrap("Unable to unzip")
-			.extract(dep_dir.join(&dep.id))
+			.extract(dep_dir.join(id))
 			.nice_unwrap("Unable to extract  package");
 		
-		// add a note saying if the dependencey is required or not (for cmake to 
-		// know if to link or not)
-		fs::write(
-			dep_dir.join(dep.id).join("-dep-options.json"),
-			format!(r#"{{ "required": {} }}"#,
-					if dep.importance == DependencyImportance::Required ||
-						dep.required.is_some() && dep.required.unwrap() { "true" } else { "false" })
-		).nice_unwrap("Unable to save dep options");
+		fs::write(&options, options_json).nice_unwrap("Unable to save dep options");
+	}
+
+	// keep everything that was picked, even if it couldn't be extracted now
+	remove_stale_dependencies(&dep_dir, &resolution.keys().cloned().collect());
+
+	if !changes.is_empty() && !errors {
+		write_lockfile(&input, &lockfile, changes);
 	}
 
 	if errors {
@@ -502,12 +1115,30 @@ pub fn subcommand(config: &mut Config, cmd: Project) {
 		Project::ClearCache => clear_cache(
             &std::env::current_dir().unwrap()
         ),
-		Project::Check { install_dir, externals, dont_update_index } => check_dependencies(
+		Project::LintResources => lint_project_resources(
+            &std::env::current_dir().unwrap()
+        ),
+		Project::Validate => validate_project(
+            &std::env::current_dir().unwrap()
+        ),
+		Project::Schema { output } => print_schema(output),
+		Project::Version { command: VersionCommand::Bump { part, pre } } => bump_version(
+            &std::env::current_dir().unwrap(), part, pre
+        ),
+		Project::Dependency { command } => dependency_subcommand(
+            config, &std::env::current_dir().unwrap(), command
+        ),
+		Project::Migrate { yes } => migrate_project(
+            &std::env::current_dir().unwrap(), yes
+        ),
+		Project::Check { install_dir, externals, dont_update_index, update, locked } => check_dependencies(
             config,
             std::env::current_dir().unwrap(),
             install_dir.unwrap_or("build".into()),
             externals,
-			dont_update_index
+			dont_update_index,
+			update,
+			locked
         ),
         Project::Publish { package } => publish_project(
             config, &std::env::current_dir().unwrap(), package
@@ -2,6 +2,7 @@ use clap::Subcommand;
 use colored::Colorize;
 use crate::config::Config;
 use crate::util::logging::ask_confirm;
+use crate::util::version;
 use git2::build::RepoBuilder;
 use git2::{FetchOptions, RemoteCallbacks, Repository, SubmoduleUpdateOptions};
 use reqwest::header::{USER_AGENT, AUTHORIZATION};
@@ -365,7 +366,7 @@ fn switch_to_tag(config: &mut Config, repo: &Repository) {
 		.iter()
 		.flatten()
 	{
-		if let Ok(version) = Version::parse(tag.strip_prefix('v').unwrap_or(tag)) {
+		if let Ok(version) = version::parse_version(tag) {
 			if latest_version.as_ref().is_none() || &version > latest_version.as_ref().unwrap() {
 				latest_version = Some(version);
 			}
@@ -482,11 +483,9 @@ fn set_sdk_path(path: PathBuf, do_move: bool) {
 }
 
 pub fn get_version() -> Version {
-	Version::parse(
-		fs::read_to_string(Config::sdk_path().join("VERSION"))
-			.nice_unwrap("Unable to read SDK version, make sure you are using SDK v0.4.2 or later")
-			.as_str()
-			.trim(),
+	version::parse_version(
+		&fs::read_to_string(Config::sdk_path().join("VERSION"))
+			.nice_unwrap("Unable to read SDK version, make sure you are using SDK v0.4.2 or later"),
 	)
 	.nice_unwrap("Invalid SDK version")
 }
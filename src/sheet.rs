@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Subcommand;
+
+use crate::util::spritesheet;
+use crate::{done, info, warn, NiceUnwrap};
+
+#[derive(Subcommand, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub enum Sheet {
+	/// Unpack a cocos2d spritesheet back into individual sprites
+	Unpack {
+		/// Location of the spritesheet's .plist file
+		plist: PathBuf,
+
+		/// Folder to place the sprites in. If not provided, a folder named
+		/// after the sheet is created next to it
+		#[clap(short, long)]
+		output: Option<PathBuf>,
+	},
+}
+
+fn unpack_sheet(plist_path: &Path, output: Option<PathBuf>) {
+	let sheet = spritesheet::read_sheet_plist(plist_path)
+		.nice_unwrap(format!("Unable to read spritesheet '{}'", plist_path.display()));
+
+	let texture = spritesheet::read_sheet_texture(&sheet.texture);
+
+	let output = output.unwrap_or_else(|| plist_path.with_extension(""));
+	fs::create_dir_all(&output).nice_unwrap("Unable to create output directory");
+
+	info!("Unpacking {} frames", sheet.frames.len());
+
+	for frame in &sheet.frames {
+		if frame.x + if frame.rotated { frame.height } else { frame.width } > texture.width()
+			|| frame.y + if frame.rotated { frame.width } else { frame.height } > texture.height()
+		{
+			warn!("Frame '{}' is outside of the texture, skipping", frame.name);
+			continue;
+		}
+
+		// Frame names may be namespaced like `mod.id/sprite.png`
+		let name = Path::new(&frame.name).file_name().nice_unwrap(
+			format!("Invalid frame name '{}'", frame.name)
+		);
+
+		let file = if Path::new(name).extension().is_some_and(|x| x == "png") {
+			output.join(name)
+		} else {
+			output.join(name.to_string_lossy().to_string() + ".png")
+		};
+
+		spritesheet::extract_frame(&texture, frame)
+			.save(file)
+			.nice_unwrap(format!("Unable to save frame '{}'", frame.name));
+	}
+
+	done!("Unpacked spritesheet into {}", output.display());
+}
+
+pub fn subcommand(cmd: Sheet) {
+	match cmd {
+		Sheet::Unpack { plist, output } => unpack_sheet(&plist, output),
+	}
+}
@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Subcommand;
+use path_absolutize::Absolutize;
+
+use crate::config::Config;
+use crate::package::{create_resources, zip_folder, ResourceTarget};
+use crate::util::depfile;
+use crate::util::mod_file::parse_texture_pack_info;
+use crate::{cache, done, info, warn, NiceUnwrap};
+
+#[derive(Subcommand, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub enum TexturePack {
+	/// Build a texture pack from the sprites, spritesheets, fonts and files
+	/// listed in its pack.json. Resources keep their vanilla names, so they
+	/// replace the game's own
+	Build {
+		/// Location of the texture pack's folder
+		root_path: PathBuf,
+
+		/// Location of output. If not provided, the resources are placed in a
+		/// folder named after the pack next to the root path (or in
+		/// `<name>-build` if the root path already has that name)
+		#[clap(short, long)]
+		output: Option<PathBuf>,
+
+		/// Package the resources into a .zip file instead of a folder
+		#[clap(long)]
+		zip: bool,
+
+		/// Less verbose output
+		#[clap(long)]
+		shut_up: bool,
+	},
+}
+
+fn build_texture_pack(
+	config: &mut Config,
+	root_path: &Path,
+	raw_output: Option<PathBuf>,
+	zip: bool,
+	shut_up: bool,
+) {
+	// Parse pack.json
+	let mut pack_info = parse_texture_pack_info(root_path);
+
+	if !pack_info.resources.libraries.is_empty() {
+		warn!("Texture packs can't contain libraries, ignoring them");
+		pack_info.resources.libraries.clear();
+	}
+
+	let mut output = raw_output.unwrap_or_else(|| default_output(root_path, &pack_info.name));
+	if zip && output.extension().is_none() {
+		output.set_extension("zip");
+	}
+
+	// The cache is kept out of the pack, so it can be used as-is
+	let state_dir = state_dir(&output);
+	fs::create_dir_all(&state_dir).nice_unwrap("Could not create build directory");
+
+	// Zipped packs are built in the build directory and then zipped, while
+	// folders are built in place
+	let build_dir = if zip { state_dir.join("pack") } else { output.clone() };
+	fs::create_dir_all(&build_dir).nice_unwrap("Could not create output directory");
+
+	let mut cache_bundle = cache::get_cache_bundle_from_dirs(&state_dir, &build_dir);
+	let mut new_cache = cache::ResourceCache::new();
+	let old_outputs = read_outputs(&state_dir);
+
+	create_resources(
+		config,
+		&pack_info.resources,
+		ResourceTarget {
+			namespace: None,
+			cache_bundle: &mut cache_bundle,
+			cache: &mut new_cache,
+			working_dir: &build_dir,
+			output_dir: &build_dir,
+		},
+		shut_up,
+	);
+
+	new_cache.save(&state_dir);
+
+	// Files of earlier builds that aren't built anymore would otherwise stay
+	// in the pack
+	let outputs = depfile::resource_outputs(&pack_info.resources, &build_dir)
+		.into_iter()
+		.map(|x| x.strip_prefix(&build_dir).unwrap().to_path_buf())
+		.collect::<Vec<_>>();
+	for stale in old_outputs.iter().filter(|x| !outputs.contains(x)) {
+		if build_dir.join(stale).exists() {
+			info!("Removing {}", stale.display());
+			fs::remove_file(build_dir.join(stale)).nice_unwrap("Unable to remove old resource");
+		}
+	}
+	write_outputs(&state_dir, &outputs);
+
+	if zip {
+		zip_folder(&build_dir, &output);
+	} else {
+		done!("Texture pack created at {}", output.display());
+	}
+}
+
+/// Folder named after the pack next to its root, so the built pack is never
+/// picked up by the pack's own globs
+fn default_output(root_path: &Path, name: &str) -> PathBuf {
+	let root = root_path.absolutize().nice_unwrap("Invalid root path").to_path_buf();
+	let sibling = root.with_file_name(name);
+	if sibling == root {
+		root.with_file_name(format!("{name}-build"))
+	} else {
+		sibling
+	}
+}
+
+/// Directory outside of the pack for the cache of builds to `output`
+fn state_dir(output: &Path) -> PathBuf {
+	let output = output.absolutize().nice_unwrap("Invalid output path");
+	let key = sha256::digest(output.to_string_lossy().to_string());
+	dirs::cache_dir().unwrap().join(format!("_texturepack_{}", &key[..16]))
+}
+
+/// Files the previous build wrote, relative to the build directory
+fn read_outputs(state_dir: &Path) -> Vec<PathBuf> {
+	fs::read_to_string(state_dir.join("._outputs"))
+		.ok()
+		.and_then(|x| serde_json::from_str(&x).ok())
+		.unwrap_or_default()
+}
+
+fn write_outputs(state_dir: &Path, outputs: &[PathBuf]) {
+	fs::write(state_dir.join("._outputs"), serde_json::to_string(outputs).unwrap())
+		.nice_unwrap("Unable to save list of built files");
+}
+
+pub fn subcommand(config: &mut Config, cmd: TexturePack) {
+	match cmd {
+		TexturePack::Build {
+			root_path,
+			output,
+			zip,
+			shut_up,
+		} => build_texture_pack(config, &root_path, output, zip, shut_up),
+	}
+}
@@ -0,0 +1,296 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::num::{NonZeroU32, NonZeroU8};
+use std::path::{Path, PathBuf};
+
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+use crate::cache::{CacheBundle, ResourceCache};
+use crate::mod_file::SoundResources;
+use crate::{done, fatal, info, warn, NiceUnwrap};
+
+/// Sample rates FMOD plays without resampling on every platform
+const COMMON_SAMPLE_RATES: &[u32] = &[22050, 32000, 44100, 48000];
+
+/// Decoded audio, with samples interleaved and normalized to -1.0..1.0
+pub struct Audio {
+	pub sample_rate: u32,
+	pub channels: u16,
+	pub samples: Vec<f32>,
+}
+
+impl Audio {
+	pub fn duration(&self) -> f32 {
+		self.samples.len() as f32 / self.channels.max(1) as f32 / self.sample_rate.max(1) as f32
+	}
+
+	/// Peak level in dBFS, or None if the sound is silent
+	pub fn peak(&self) -> Option<f32> {
+		let peak = self.samples.iter().fold(0f32, |acc, x| acc.max(x.abs()));
+		(peak > 0.0).then(|| 20.0 * peak.log10())
+	}
+
+	/// Scale the samples so the peak is at the target level in dBFS
+	pub fn normalize(&mut self, target: f32) {
+		let peak = self.samples.iter().fold(0f32, |acc, x| acc.max(x.abs()));
+		if peak > 0.0 {
+			let gain = 10f32.powf(target / 20.0) / peak;
+			self.samples.iter_mut().for_each(|x| *x *= gain);
+		}
+	}
+}
+
+fn decode_wav(path: &Path) -> Result<Audio, String> {
+	let reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+	let spec = reader.spec();
+
+	let samples = match spec.sample_format {
+		hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<Vec<_>, _>>(),
+		hound::SampleFormat::Int => {
+			let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+			reader
+				.into_samples::<i32>()
+				.map(|x| x.map(|x| x as f32 * scale))
+				.collect()
+		}
+	}
+	.map_err(|e| e.to_string())?;
+
+	Ok(Audio {
+		sample_rate: spec.sample_rate,
+		channels: spec.channels,
+		samples,
+	})
+}
+
+fn decode_ogg(path: &Path) -> Result<Audio, String> {
+	let file = File::open(path).map_err(|e| e.to_string())?;
+	let mut reader = lewton::inside_ogg::OggStreamReader::new(BufReader::new(file))
+		.map_err(|e| e.to_string())?;
+
+	let mut samples = Vec::new();
+	while let Some(packet) = reader.read_dec_packet_itl().map_err(|e| e.to_string())? {
+		samples.extend(packet.into_iter().map(|x| x as f32 / 32768.0));
+	}
+
+	Ok(Audio {
+		sample_rate: reader.ident_hdr.audio_sample_rate,
+		channels: reader.ident_hdr.audio_channels as u16,
+		samples,
+	})
+}
+
+/// Read the sample rate and channel count from the first frame of an MP3.
+/// MP3s aren't decoded, so they can only be checked and copied
+fn read_mp3_format(path: &Path) -> Result<(u32, u16), String> {
+	let data = fs::read(path).map_err(|e| e.to_string())?;
+
+	// Skip the ID3v2 tag, whose size is stored as a 28-bit syncsafe integer
+	let mut start = 0;
+	if data.starts_with(b"ID3") && data.len() >= 10 {
+		start = 10 + data[6..10].iter().fold(0, |acc, x| (acc << 7) | (*x as usize & 0x7f));
+	}
+
+	let header = data
+		.get(start..)
+		.and_then(|data| {
+			data.windows(4).find(|x| {
+				x[0] == 0xff
+					&& x[1] & 0xe0 == 0xe0
+					&& (x[1] >> 1) & 3 != 0
+					&& (x[2] >> 2) & 3 != 3
+			})
+		})
+		.ok_or("No MPEG audio frame found")?;
+
+	let base_rate = [44100, 48000, 32000][((header[2] >> 2) & 3) as usize];
+	let sample_rate = match (header[1] >> 3) & 3 {
+		3 => base_rate,
+		2 => base_rate / 2,
+		0 => base_rate / 4,
+		_ => return Err("Invalid MPEG version".into()),
+	};
+	let channels = if header[3] >> 6 == 3 { 1 } else { 2 };
+
+	Ok((sample_rate, channels))
+}
+
+/// Read the sample rate and channel count of a sound from its headers,
+/// without decoding it
+fn read_format(path: &Path, ext: &str) -> Result<(u32, u16), String> {
+	match ext {
+		"wav" => {
+			let spec = hound::WavReader::open(path).map_err(|e| e.to_string())?.spec();
+			Ok((spec.sample_rate, spec.channels))
+		}
+		"ogg" => {
+			let file = File::open(path).map_err(|e| e.to_string())?;
+			let reader = lewton::inside_ogg::OggStreamReader::new(BufReader::new(file))
+				.map_err(|e| e.to_string())?;
+			Ok((reader.ident_hdr.audio_sample_rate, reader.ident_hdr.audio_channels as u16))
+		}
+		_ => read_mp3_format(path),
+	}
+}
+
+fn encode_ogg(audio: &Audio, quality: f32, path: &Path) -> Result<(), String> {
+	// The encoder takes a buffer per channel
+	let channels = audio.channels as usize;
+	let mut planar = vec![Vec::with_capacity(audio.samples.len() / channels); channels];
+	for frame in audio.samples.chunks(channels) {
+		for (channel, sample) in frame.iter().enumerate() {
+			planar[channel].push(*sample);
+		}
+	}
+
+	let mut out = Vec::new();
+	let mut encoder = VorbisEncoderBuilder::new(
+		NonZeroU32::new(audio.sample_rate).ok_or("Invalid sample rate")?,
+		NonZeroU8::new(audio.channels as u8).ok_or("Invalid channel count")?,
+		&mut out,
+	)
+	.map_err(|e| e.to_string())?
+	.bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+		target_quality: quality.clamp(-0.2, 1.0),
+	})
+	.build()
+	.map_err(|e| e.to_string())?;
+
+	const BLOCK_SIZE: usize = 4096;
+	for start in (0..planar[0].len()).step_by(BLOCK_SIZE) {
+		let block = planar
+			.iter()
+			.map(|x| &x[start..(start + BLOCK_SIZE).min(x.len())])
+			.collect::<Vec<_>>();
+		encoder.encode_audio_block(&block).map_err(|e| e.to_string())?;
+	}
+	encoder.finish().map_err(|e| e.to_string())?;
+
+	fs::write(path, out).map_err(|e| e.to_string())
+}
+
+/// Check a sound's sample rate and channel count, returning whether the
+/// sound is fit to ship
+fn check_format(path: &Path, sample_rate: u32, channels: u16) -> bool {
+	let mut ok = true;
+	if !COMMON_SAMPLE_RATES.contains(&sample_rate) {
+		warn!(
+			"{}: Unusual sample rate {} Hz, FMOD will have to resample it",
+			path.display(), sample_rate
+		);
+	}
+	if sample_rate > 48000 {
+		warn!("{}: Sample rates above 48000 Hz only make the file bigger", path.display());
+	}
+	if channels == 0 || channels > 2 {
+		warn!("{}: Sounds should be mono or stereo, found {} channels", path.display(), channels);
+		ok = channels != 0;
+	}
+	ok
+}
+
+fn sound_cache_name(path: &Path, working_dir: &Path) -> PathBuf {
+	path.strip_prefix(working_dir).unwrap_or(path).to_path_buf()
+}
+
+/// Validate sounds and copy them into the output directory, transcoding
+/// WAVs and OGGs to OGG Vorbis if requested
+pub fn build_sounds(
+	sounds: &SoundResources,
+	output_dir: &Path,
+	working_dir: &Path,
+	cache_bundle: &mut Option<CacheBundle>,
+	cache: &mut ResourceCache,
+	shut_up: bool,
+) {
+	if !shut_up {
+		info!("Checking sounds");
+	}
+
+	for path in &sounds.files {
+		let ext = path
+			.extension()
+			.and_then(|x| x.to_str())
+			.map(|x| x.to_lowercase())
+			.unwrap_or_default();
+		if !["wav", "ogg", "mp3"].contains(&ext.as_str()) {
+			fatal!(
+				"Unsupported sound '{}', expected a .wav, .ogg or .mp3 file",
+				path.display()
+			);
+		}
+
+		let (sample_rate, channels) = read_format(path, &ext)
+			.nice_unwrap(format!("Unable to read sound '{}'", path.display()));
+		if !check_format(path, sample_rate, channels) {
+			fatal!("Sound '{}' can't be used", path.display());
+		}
+
+		if ext == "mp3" {
+			if sounds.transcode {
+				warn!("{}: MP3s can't be transcoded, copying as-is", path.display());
+			}
+			fs::copy(path, output_dir.join(path.file_name().unwrap()))
+				.nice_unwrap(format!("Unable to copy sound '{}'", path.display()));
+			continue;
+		}
+
+		let output = output_dir.join(path.with_extension("ogg").file_name().unwrap());
+		let cache_name = sound_cache_name(&output, working_dir);
+
+		// Transcoding is slow, so reuse the previous result without even
+		// decoding the sound if possible
+		if sounds.transcode {
+			let cached = cache_bundle.as_mut().is_some_and(|bundle| {
+				let Some(name) = bundle
+					.cache
+					.fetch_sound(path, sounds)
+					.map(|x| x.to_str().unwrap().to_string())
+				else {
+					return false;
+				};
+				bundle.try_extract_cached_into(&name, &output)
+			});
+			if cached {
+				if !shut_up {
+					info!("{}: Using cached transcode", path.file_name().unwrap().to_str().unwrap());
+				}
+				cache.add_sound(path, sounds, cache_name);
+				continue;
+			}
+		}
+
+		let decoded = if ext == "wav" { decode_wav(path) } else { decode_ogg(path) };
+		let mut audio = decoded.nice_unwrap(format!("Unable to decode sound '{}'", path.display()));
+
+		let peak = audio.peak();
+		if !shut_up {
+			info!(
+				"{}: {:.2}s, {} Hz, {} channel(s), {}",
+				path.file_name().unwrap().to_str().unwrap(),
+				audio.duration(),
+				audio.sample_rate,
+				audio.channels,
+				peak.map(|x| format!("peak {x:.1} dBFS")).unwrap_or("silent".into())
+			);
+		}
+		if peak.is_some_and(|x| x >= 0.0) {
+			warn!("{}: Sound is clipping", path.display());
+		}
+
+		if !sounds.transcode {
+			fs::copy(path, output_dir.join(path.file_name().unwrap()))
+				.nice_unwrap(format!("Unable to copy sound '{}'", path.display()));
+			continue;
+		}
+
+		if let Some(target) = sounds.normalize {
+			audio.normalize(target);
+		}
+		encode_ogg(&audio, sounds.quality, &output)
+			.nice_unwrap(format!("Unable to transcode sound '{}'", path.display()));
+		cache.add_sound(path, sounds, cache_name);
+	}
+
+	done!("Processed {} sound(s)", sounds.files.len());
+}
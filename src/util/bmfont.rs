@@ -1,5 +1,8 @@
 use crate::cache::CacheBundle;
-use crate::mod_file::BitmapFont;
+use crate::mod_file::{BitmapFont, TextureOutput};
+use crate::optimize::write_png;
+use crate::pvr::write_pvr_ccz;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
@@ -7,12 +10,14 @@ use texture_packer::exporter::ImageExporter;
 use texture_packer::texture::Texture;
 use texture_packer::TexturePacker;
 use texture_packer::TexturePackerConfig;
+use ttf_parser::gpos::{PairAdjustment, PositioningSubtable};
+use ttf_parser::opentype_layout::{Coverage, LookupSubtable};
+use ttf_parser::{kern, Face, GlyphId, OutlineBuilder, RawFace, Tag};
+use ab_glyph_rasterizer::{point, Point, Rasterizer};
 
-use crate::{done, info, NiceUnwrap};
+use crate::{done, info, warn, NiceUnwrap};
 use image::{Rgba, RgbaImage};
 
-use super::mod_file::ModFileInfo;
-
 struct RenderedChar {
 	id: char,
 	img: RgbaImage,
@@ -113,17 +118,286 @@ fn generate_char(
 	}))
 }
 
-fn initialize_font_bundle(
-	bundle: &FontBundle,
-	font: &BitmapFont,
-	factor: u32,
-	_mod_info: &ModFileInfo,
-) -> PathBuf {
+/// Kerning pairs read from the font, with amounts in font units
+struct KerningPairs {
+	units_per_em: f32,
+	pairs: Vec<(char, char, i16)>,
+}
+
+/// Where glyphs of a loaded font are rasterized from
+enum GlyphSource<'a> {
+	/// Static fonts are rasterized by fontdue
+	Static(fontdue::Font),
+	/// fontdue can't apply font variations, so variable font instances are
+	/// outlined by ttf-parser from a face with the variations already set
+	/// and rasterized here
+	Variable(Box<Face<'a>>),
+}
+
+/// A font loaded once and shared between all of its resolution tiers
+struct LoadedFont<'a> {
+	chars: Vec<char>,
+	glyphs: GlyphSource<'a>,
+	kerning: KerningPairs,
+}
+
+impl LoadedFont<'_> {
+	fn rasterize(&self, c: char, px: f32) -> (fontdue::Metrics, Vec<u8>) {
+		match &self.glyphs {
+			GlyphSource::Static(font) => font.rasterize(c, px),
+			GlyphSource::Variable(face) => rasterize_outline(face, c, px),
+		}
+	}
+
+	fn metrics(&self, c: char, px: f32) -> fontdue::Metrics {
+		match &self.glyphs {
+			GlyphSource::Static(font) => font.metrics(c, px),
+			GlyphSource::Variable(face) => outline_metrics(face, c, px).0,
+		}
+	}
+
+	fn horizontal_line_metrics(&self, px: f32) -> Option<fontdue::LineMetrics> {
+		match &self.glyphs {
+			GlyphSource::Static(font) => font.horizontal_line_metrics(px),
+			GlyphSource::Variable(face) => {
+				let scale = px / face.units_per_em() as f32;
+				let ascent = face.ascender() as f32 * scale;
+				let descent = face.descender() as f32 * scale;
+				let line_gap = face.line_gap() as f32 * scale;
+				Some(fontdue::LineMetrics {
+					ascent,
+					descent,
+					line_gap,
+					new_line_size: ascent - descent + line_gap,
+				})
+			}
+		}
+	}
+}
+
+/// Metrics of a glyph in the same layout fontdue uses, along with the pixel
+/// bounds of its bitmap
+fn outline_metrics(face: &Face, c: char, px: f32) -> (fontdue::Metrics, ttf_parser::Rect) {
+	let scale = px / face.units_per_em() as f32;
+	let glyph = face.glyph_index(c).unwrap_or(GlyphId(0));
+	let advance_width = face.glyph_hor_advance(glyph).unwrap_or(0) as f32 * scale;
+
+	let bbox = face.glyph_bounding_box(glyph).unwrap_or(ttf_parser::Rect {
+		x_min: 0,
+		y_min: 0,
+		x_max: 0,
+		y_max: 0,
+	});
+	let pixels = ttf_parser::Rect {
+		x_min: (bbox.x_min as f32 * scale).floor() as i16,
+		y_min: (bbox.y_min as f32 * scale).floor() as i16,
+		x_max: (bbox.x_max as f32 * scale).ceil() as i16,
+		y_max: (bbox.y_max as f32 * scale).ceil() as i16,
+	};
+
+	(fontdue::Metrics {
+		xmin: pixels.x_min as i32,
+		ymin: pixels.y_min as i32,
+		width: pixels.width() as usize,
+		height: pixels.height() as usize,
+		advance_width,
+		advance_height: 0.0,
+		bounds: fontdue::OutlineBounds {
+			xmin: bbox.x_min as f32 * scale,
+			ymin: bbox.y_min as f32 * scale,
+			width: bbox.width() as f32 * scale,
+			height: bbox.height() as f32 * scale,
+		},
+	}, pixels)
+}
+
+/// Draws a glyph outline given in font units into a bitmap with its origin
+/// at the top left
+struct OutlineRasterizer {
+	rasterizer: Rasterizer,
+	scale: f32,
+	origin: Point,
+	start: Point,
+	last: Point,
+}
+
+impl OutlineRasterizer {
+	fn point(&self, x: f32, y: f32) -> Point {
+		point(x * self.scale - self.origin.x, self.origin.y - y * self.scale)
+	}
+}
+
+impl OutlineBuilder for OutlineRasterizer {
+	fn move_to(&mut self, x: f32, y: f32) {
+		self.start = self.point(x, y);
+		self.last = self.start;
+	}
+
+	fn line_to(&mut self, x: f32, y: f32) {
+		let p = self.point(x, y);
+		self.rasterizer.draw_line(self.last, p);
+		self.last = p;
+	}
+
+	fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+		let p = self.point(x, y);
+		self.rasterizer.draw_quad(self.last, self.point(x1, y1), p);
+		self.last = p;
+	}
+
+	fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+		let p = self.point(x, y);
+		self.rasterizer.draw_cubic(self.last, self.point(x1, y1), self.point(x2, y2), p);
+		self.last = p;
+	}
+
+	fn close(&mut self) {
+		if self.last != self.start {
+			self.rasterizer.draw_line(self.last, self.start);
+		}
+		self.last = self.start;
+	}
+}
+
+fn rasterize_outline(face: &Face, c: char, px: f32) -> (fontdue::Metrics, Vec<u8>) {
+	let (metrics, pixels) = outline_metrics(face, c, px);
+	if metrics.width == 0 || metrics.height == 0 {
+		return (metrics, Vec::new());
+	}
+
+	let mut outline = OutlineRasterizer {
+		rasterizer: Rasterizer::new(metrics.width, metrics.height),
+		scale: px / face.units_per_em() as f32,
+		origin: point(pixels.x_min as f32, pixels.y_max as f32),
+		start: point(0.0, 0.0),
+		last: point(0.0, 0.0),
+	};
+	face.outline_glyph(face.glyph_index(c).unwrap_or(GlyphId(0)), &mut outline);
+
+	let mut data = vec![0u8; metrics.width * metrics.height];
+	outline.rasterizer.for_each_pixel(|i, alpha| {
+		data[i] = (alpha.clamp(0.0, 1.0) * 255.0) as u8;
+	});
+	(metrics, data)
+}
+
+/// Get the axis values of a named instance from the font's fvar table
+fn named_instance(data: &[u8], face_index: u32, face: &Face, name: &str) -> Result<Vec<(Tag, f32)>, String> {
+	let fvar = RawFace::from_slice(data, face_index)
+		.ok()
+		.and_then(|raw| raw.table(Tag::from_bytes(b"fvar")))
+		.ok_or("Font has no fvar table")?;
+
+	let read_u16 = |at: usize| fvar.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+	let read_fixed = |at: usize| fvar
+		.get(at..at + 4)
+		.map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f32 / 65536.0);
+
+	let axes: Vec<_> = face.variation_axes().into_iter().map(|a| a.tag).collect();
+	let malformed = || "Malformed fvar table".to_string();
+
+	// Instance records follow the axis records
+	let axes_offset = read_u16(4).ok_or_else(malformed)? as usize;
+	let axis_size = read_u16(10).ok_or_else(malformed)? as usize;
+	let instance_count = read_u16(12).ok_or_else(malformed)? as usize;
+	let instance_size = read_u16(14).ok_or_else(malformed)? as usize;
+	let instances_offset = axes_offset + axes.len() * axis_size;
+
+	let mut available = Vec::new();
+	for i in 0..instance_count {
+		let offset = instances_offset + i * instance_size;
+		let name_id = read_u16(offset).ok_or_else(malformed)?;
+		let Some(instance_name) = face
+			.names()
+			.into_iter()
+			.filter(|n| n.name_id == name_id)
+			.find_map(|n| n.to_string())
+		else {
+			continue;
+		};
+
+		if instance_name.eq_ignore_ascii_case(name) {
+			return axes
+				.iter()
+				.enumerate()
+				.map(|(a, tag)| {
+					read_fixed(offset + 4 + a * 4)
+						.map(|value| (*tag, value))
+						.ok_or_else(malformed)
+				})
+				.collect();
+		}
+		available.push(instance_name);
+	}
+
+	Err(format!(
+		"Font has no instance named '{}'. Available instances: {}",
+		name,
+		available.join(", ")
+	))
+}
+
+/// Resolve the axis values of the font instance to use, or `None` if the
+/// font should be used as-is
+fn resolve_variations(data: &[u8], face: &Face, font: &BitmapFont) -> Result<Option<Vec<(Tag, f32)>>, String> {
+	if font.instance.is_none() && font.variations.is_empty() {
+		return Ok(None);
+	}
+	if !face.is_variable() {
+		return Err("Instances and variations can only be used with variable fonts".into());
+	}
+
+	let mut coords = match &font.instance {
+		Some(name) => named_instance(data, font.face_index, face, name)?,
+		None => Vec::new(),
+	};
+
+	for (axis, value) in &font.variations {
+		let tag = match axis.as_str() {
+			"weight" => "wght",
+			"width" => "wdth",
+			"slant" => "slnt",
+			"italic" => "ital",
+			"opticalSize" => "opsz",
+			tag => tag,
+		};
+		let Some(info) = face
+			.variation_axes()
+			.into_iter()
+			.find(|a| a.tag.to_bytes() == tag.as_bytes())
+		else {
+			return Err(format!(
+				"Font has no variation axis '{}'. Available axes: {}",
+				axis,
+				face.variation_axes()
+					.into_iter()
+					.map(|a| a.tag.to_string())
+					.collect::<Vec<_>>()
+					.join(", ")
+			));
+		};
+		if *value < info.min_value || *value > info.max_value {
+			warn!(
+				"Value {} for axis '{}' is outside of its range {}-{}, clamping",
+				value, axis, info.min_value, info.max_value
+			);
+		}
+
+		coords.retain(|(t, _)| *t != info.tag);
+		coords.push((info.tag, value.clamp(info.min_value, info.max_value)));
+	}
+
+	Ok(Some(coords))
+}
+
+pub const DEFAULT_CHARSET: &str = "32-126,8226";
+
+fn parse_charset(font: &BitmapFont) -> Vec<char> {
 	// Get all characters from the charset format
-	let chars: Vec<char> = font
+	font
 		.charset
 		.as_deref()
-		.unwrap_or("32-126,8226")
+		.unwrap_or(DEFAULT_CHARSET)
 		.split(',')
 		.map(|x| {
 			x.split('-')
@@ -135,23 +409,249 @@ fn initialize_font_bundle(
 			*x.first().unwrap()..*x.last().unwrap() + 1
 		})
 		.map(|c| char::from_u32(c).unwrap())
-		.collect();
+		// Ranges may overlap
+		.collect::<BTreeSet<_>>()
+		.into_iter()
+		.collect()
+}
 
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+	Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+/// Pair sets of a pair adjustment subtable in the first format, read from
+/// its raw data as ttf-parser can only look pairs up by their second glyph
+struct PairSets<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> LookupSubtable<'a> for PairSets<'a> {
+	fn parse(data: &'a [u8], kind: u16) -> Option<Self> {
+		match kind {
+			2 => (read_u16(data, 0)? == 1).then_some(PairSets { data }),
+			// Extension subtables point to the actual subtable
+			9 => {
+				let offset = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?) as usize;
+				Self::parse(data.get(offset..)?, read_u16(data, 2)?)
+			}
+			_ => None,
+		}
+	}
+}
+
+impl PairSets<'_> {
+	/// Second glyphs and x advance adjustments of the pair set at a coverage
+	/// index
+	fn pairs(&self, index: u16) -> Option<Vec<(u16, i16)>> {
+		let first_format = read_u16(self.data, 4)?;
+		let second_format = read_u16(self.data, 6)?;
+		if index >= read_u16(self.data, 8)? {
+			return None;
+		}
+		let set = self.data.get(read_u16(self.data, 10 + 2 * index as usize)? as usize..)?;
+
+		// Each value record has 2 bytes for each field in its format, and the
+		// x advance comes after the placements
+		let fields = |format: u16| (format & 0xFF).count_ones() as usize;
+		let record_len = 2 + 2 * (fields(first_format) + fields(second_format));
+		if first_format & 0x4 == 0 {
+			return Some(Vec::new());
+		}
+		let advance = 2 + 2 * fields(first_format & 0x3);
+
+		(0..read_u16(set, 0)? as usize)
+			.map(|i| {
+				let record = 2 + i * record_len;
+				Some((read_u16(set, record)?, read_u16(set, record + advance)? as i16))
+			})
+			.collect()
+	}
+}
+
+/// Glyphs of a coverage table along with their coverage indices
+fn covered_glyphs(coverage: Coverage) -> Vec<(u16, u16)> {
+	match coverage {
+		Coverage::Format1 { glyphs } => glyphs
+			.into_iter()
+			.enumerate()
+			.map(|(i, glyph)| (glyph.0, i as u16))
+			.collect(),
+		Coverage::Format2 { records } => records
+			.into_iter()
+			.flat_map(|x| (x.start.0..=x.end.0).map(move |glyph| (glyph, x.value.wrapping_add(glyph - x.start.0))))
+			.collect(),
+	}
+}
+
+/// Read the kerning pairs between characters of the charset. For variable
+/// fonts this is the face of the selected instance, but ttf-parser doesn't
+/// apply variation deltas to pair adjustments, so the amounts are those of
+/// the default instance
+fn read_kerning_pairs(face: &Face, chars: &[char]) -> KerningPairs {
+	// Map glyphs back to the characters in the charset that use them, so
+	// only pairs within the charset are ever considered
+	let mut glyphs: BTreeMap<u16, Vec<char>> = BTreeMap::new();
+	for c in chars {
+		if let Some(glyph) = face.glyph_index(*c) {
+			glyphs.entry(glyph.0).or_default().push(*c);
+		}
+	}
+
+	let mut kerns: HashMap<(u16, u16), i16> = HashMap::new();
+	let mut add_kern = |left: u16, right: u16, amount: i16| {
+		// The first table to define a pair wins, so GPOS takes precedence
+		// over the legacy kern table
+		if amount != 0 {
+			kerns.entry((left, right)).or_insert(amount);
+		}
+	};
+
+	// Modern fonts store their kerning in the GPOS table, under lookups
+	// referenced by the 'kern' feature
+	if let Some(gpos) = face.tables().gpos {
+		let lookups = gpos
+			.features
+			.into_iter()
+			.filter(|feature| feature.tag == Tag::from_bytes(b"kern"))
+			.flat_map(|feature| feature.lookup_indices)
+			.collect::<BTreeSet<_>>();
+
+		for lookup in lookups.into_iter().filter_map(|i| gpos.lookups.get(i)) {
+			// Subtables are read by index, as iterating stops at the first one
+			// that can't be read
+			for index in 0..lookup.subtables.len() {
+				let Some(PositioningSubtable::Pair(pairs)) = lookup.subtables.get::<PositioningSubtable>(index) else {
+					continue;
+				};
+				match pairs {
+					// Read the pairs the font lists for each covered glyph
+					PairAdjustment::Format1 { coverage, .. } => {
+						let Some(sets) = lookup.subtables.get::<PairSets>(index) else {
+							continue;
+						};
+						for (left, set) in covered_glyphs(coverage) {
+							if !glyphs.contains_key(&left) {
+								continue;
+							}
+							for (right, amount) in sets.pairs(set).unwrap_or_default() {
+								if glyphs.contains_key(&right) {
+									add_kern(left, right, amount);
+								}
+							}
+						}
+					}
+					// Group the right glyphs by class so each class pair is only
+					// looked up once
+					PairAdjustment::Format2 { coverage, classes, matrix } => {
+						let mut right_classes: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+						for right in glyphs.keys() {
+							right_classes.entry(classes.1.get(GlyphId(*right))).or_default().push(*right);
+						}
+						for left in glyphs.keys() {
+							if !coverage.contains(GlyphId(*left)) {
+								continue;
+							}
+							let left_class = classes.0.get(GlyphId(*left));
+							for (right_class, rights) in &right_classes {
+								if let Some((value, _)) = matrix.get((left_class, *right_class)) {
+									for right in rights {
+										add_kern(*left, *right, value.x_advance);
+									}
+								}
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+
+	// Older fonts use the kern table, of which only the ordered pair list
+	// format is used in practice
+	if let Some(kern) = face.tables().kern {
+		for subtable in kern.subtables {
+			if !subtable.horizontal || subtable.variable || subtable.has_cross_stream {
+				continue;
+			}
+			if let kern::Format::Format0(table) = subtable.format {
+				for pair in table.pairs {
+					if glyphs.contains_key(&pair.left().0) && glyphs.contains_key(&pair.right().0) {
+						add_kern(pair.left().0, pair.right().0, pair.value);
+					}
+				}
+			}
+		}
+	}
+
+	let mut pairs = kerns
+		.into_iter()
+		.flat_map(|((left, right), amount)| {
+			let rights = &glyphs[&right];
+			glyphs[&left]
+				.iter()
+				.flat_map(move |l| rights.iter().map(move |r| (*l, *r, amount)))
+		})
+		.collect::<Vec<_>>();
+	pairs.sort();
+
+	KerningPairs {
+		units_per_em: face.units_per_em() as f32,
+		pairs,
+	}
+}
+
+fn load_font<'a>(font: &BitmapFont, data: &'a [u8]) -> LoadedFont<'a> {
+	let chars = parse_charset(font);
+
+	let mut face = Face::from_slice(data, font.face_index)
+		.nice_unwrap(format!("Unable to parse font '{}'", font.path.display()));
+	let variations = resolve_variations(data, &face, font)
+		.nice_unwrap(format!("Unable to use font '{}'", font.path.display()));
+
+	let glyphs = match variations {
+		Some(coords) => {
+			for (axis, value) in coords {
+				face.set_variation(axis, value);
+			}
+			GlyphSource::Variable(Box::new(face.clone()))
+		}
+		None => GlyphSource::Static(
+			fontdue::Font::from_bytes(
+				data,
+				fontdue::FontSettings {
+					collection_index: font.face_index,
+					..Default::default()
+				},
+			)
+			.nice_unwrap(format!("Unable to parse font '{}'", font.path.display()))
+		),
+	};
+
+	let kerning = read_kerning_pairs(&face, &chars);
+
+	LoadedFont {
+		chars,
+		glyphs,
+		kerning,
+	}
+}
+
+fn initialize_font_bundle(
+	bundle: &FontBundle,
+	font: &BitmapFont,
+	loaded: &LoadedFont,
+	factor: u32,
+	output: &TextureOutput,
+) -> u64 {
 	// Scaled font size
 	let scaled_size = font.size / factor;
 
-	// Read & parse source .ttf file
-	let ttf_font = fontdue::Font::from_bytes(
-		fs::read(&font.path).unwrap(),
-		fontdue::FontSettings::default(),
-	)
-	.unwrap();
-
 	// Rasterize characters from charset using the source font
-	let rasterized_chars: Vec<_> = chars
+	let rasterized_chars: Vec<_> = loaded
+		.chars
 		.iter()
 		.filter_map(|c| {
-			let (metrics, data) = ttf_font.rasterize(*c, scaled_size as f32);
+			let (metrics, data) = loaded.rasterize(*c, scaled_size as f32);
 
 			generate_char(font, metrics, data).map(|img| RenderedChar { id: *c, img })
 		})
@@ -187,10 +687,21 @@ fn initialize_font_bundle(
 		.iter()
 		.for_each(|x| packer.pack_ref(x.id, &x.img).unwrap());
 
+	let exporter = ImageExporter::export(&packer).unwrap().to_rgba8();
+
 	// Create .png file
-	let exporter = ImageExporter::export(&packer).unwrap();
-	let mut f = fs::File::create(&bundle.png).nice_unwrap("Unable to write font .png file");
-	exporter.write_to(&mut f, image::ImageFormat::Png).unwrap();
+	let saved = if output.png() {
+		write_png(&exporter, &bundle.png, output.png_optimization)
+			.nice_unwrap("Unable to write font .png file")
+	} else {
+		0
+	};
+
+	// Create .pvr.ccz file
+	if output.pvr() {
+		write_pvr_ccz(&exporter, &bundle.pvr, output.pixel_format)
+			.nice_unwrap("Unable to write font .pvr.ccz file");
+	}
 
 	// Get all characters and their metrics (positions in the png)
 	// Add space explicitly because it's empty and not in the frames
@@ -198,10 +709,10 @@ fn initialize_font_bundle(
 	// other space characters don't get omitted
 	let mut all_chars = vec![format!(
 		"char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance={} page=0 chln=0",
-		ttf_font.metrics(' ', scaled_size as f32).advance_width
+		loaded.metrics(' ', scaled_size as f32).advance_width
 	)];
 	for (name, frame) in packer.get_frames() {
-		let metrics = ttf_font.metrics(*name, scaled_size as f32);
+		let metrics = loaded.metrics(*name, scaled_size as f32);
 		all_chars.push(format!(
 			"char id={} x={} y={} width={} height={} xoffset={} yoffset={} xadvance={} page=0 chnl=0",
 			*name as i32,
@@ -218,28 +729,27 @@ fn initialize_font_bundle(
 	// sorting
 	all_chars.sort();
 
-	// Get all kerning pairs
-	let mut all_kerning_pairs = rasterized_chars
+	// Scale the font's kerning pairs to this size, keeping only those
+	// between characters in the .fnt. The pairs are already sorted, so all
+	// packings for the same input produce identical output
+	let written = rasterized_chars.iter().map(|x| x.id).chain([' ']).collect::<HashSet<_>>();
+	let scale = scaled_size as f32 / loaded.kerning.units_per_em;
+	let all_kerning_pairs = loaded
+		.kerning
+		.pairs
 		.iter()
-		.flat_map(|left| {
-			rasterized_chars.iter().filter_map(|right| {
-				ttf_font
-					.horizontal_kern(left.id, right.id, scaled_size as f32)
-					.map(|kern| {
-						format!(
-							"kerning first={} second={} amount={}",
-							left.id, right.id, kern as i32
-						)
-					})
-			})
+		.filter(|(left, right, _)| written.contains(left) && written.contains(right))
+		.filter_map(|(left, right, amount)| {
+			let amount = (*amount as f32 * scale) as i32;
+			(amount != 0).then(|| format!(
+				"kerning first={} second={} amount={}",
+				*left as u32, *right as u32, amount
+			))
 		})
 		.collect::<Vec<_>>();
-	// Make sure all packings for the same input produce identical output by
-	// sorting
-	all_kerning_pairs.sort();
 
 	// Create .fnt file
-	let line_metrics = ttf_font
+	let line_metrics = loaded
 		.horizontal_line_metrics(scaled_size as f32)
 		.unwrap();
 	let fnt_data = format!(
@@ -247,7 +757,7 @@ fn initialize_font_bundle(
 		charset=\"\" unicode=1 stretchH=100 smooth=1 aa=1 padding=0,0,0,0 spacing=1,1\n\
 		common lineHeight={common_line_height} base={font_base} \
 		scaleW={scale_w} scaleH={scale_h} pages=1 packed=0\n\
-		page id=0 file=\"{sprite_file_name}.png\"\n\
+		page id=0 file=\"{sprite_file_name}.{texture_extension}\"\n\
 		chars count={char_count}\n\
 		{all_chars}\n\
 		kernings count={kerning_count}\n\
@@ -259,6 +769,7 @@ fn initialize_font_bundle(
 		scale_w = packer.width(),
 		scale_h = packer.height(),
 		sprite_file_name = font.name,
+		texture_extension = if output.pvr() { "pvr.ccz" } else { "png" },
 		char_count = all_chars.len(),
 		all_chars = all_chars.join("\n"),
 		kerning_count = all_kerning_pairs.len(),
@@ -266,11 +777,12 @@ fn initialize_font_bundle(
 	);
 	fs::write(&bundle.fnt, fnt_data).nice_unwrap("Unable to write font .fnt file");
 
-	PathBuf::from(font.name.to_owned() + ".png")
+	saved
 }
 
 pub struct FontBundle {
 	pub png: PathBuf,
+	pub pvr: PathBuf,
 	pub fnt: PathBuf,
 }
 
@@ -278,6 +790,8 @@ pub struct FontBundles {
 	pub sd: FontBundle,
 	pub hd: FontBundle,
 	pub uhd: FontBundle,
+	/// Bytes saved by PNG optimization while building, 0 if fetched from cache
+	pub bytes_saved: u64,
 }
 
 impl FontBundles {
@@ -285,7 +799,10 @@ impl FontBundles {
 		let mut fnt = base.to_owned();
 		fnt.set_extension("fnt");
 
-		FontBundle { png: base, fnt }
+		let mut pvr = base.to_owned();
+		pvr.set_extension("pvr.ccz");
+
+		FontBundle { png: base, pvr, fnt }
 	}
 
 	pub fn new(mut base: PathBuf) -> FontBundles {
@@ -300,6 +817,7 @@ impl FontBundles {
 			sd: FontBundles::new_file(base),
 			hd: FontBundles::new_file(hd),
 			uhd: FontBundles::new_file(uhd),
+			bytes_saved: 0,
 		}
 	}
 
@@ -332,7 +850,7 @@ pub fn get_font_bundles(
 	font: &BitmapFont,
 	working_dir: &Path,
 	cache: &mut Option<CacheBundle>,
-	mod_info: &ModFileInfo,
+	output: &TextureOutput,
 	shut_up: bool,
 ) -> FontBundles {
 	// todo: we really should add a global verbosity option and logging levels for that
@@ -343,19 +861,22 @@ pub fn get_font_bundles(
 
 	if let Some(cache_bundle) = cache {
 		// Cache found
-		if let Some(p) = cache_bundle.cache.fetch_font_bundles(font) {
+		if let Some(p) = cache_bundle.cache.fetch_font_bundles(font, output) {
 			if !shut_up {
 				info!("Using cached files");
 			}
 			let bundles = FontBundles::new(p.to_path_buf());
 
 			// Extract all files
-			extract_from_cache(&bundles.sd.png, working_dir, cache_bundle, shut_up);
-			extract_from_cache(&bundles.sd.fnt, working_dir, cache_bundle, shut_up);
-			extract_from_cache(&bundles.hd.png, working_dir, cache_bundle, shut_up);
-			extract_from_cache(&bundles.hd.fnt, working_dir, cache_bundle, shut_up);
-			extract_from_cache(&bundles.uhd.png, working_dir, cache_bundle, shut_up);
-			extract_from_cache(&bundles.uhd.fnt, working_dir, cache_bundle, shut_up);
+			for bundle in [&bundles.sd, &bundles.hd, &bundles.uhd] {
+				if output.png() {
+					extract_from_cache(&bundle.png, working_dir, cache_bundle, shut_up);
+				}
+				if output.pvr() {
+					extract_from_cache(&bundle.pvr, working_dir, cache_bundle, shut_up);
+				}
+				extract_from_cache(&bundle.fnt, working_dir, cache_bundle, shut_up);
+			}
 
 			done!("Fetched {} from cache", font.name.bright_yellow());
 			return bundles;
@@ -365,18 +886,23 @@ pub fn get_font_bundles(
 	if !shut_up {
 		info!("Font is not cached, building from scratch");
 	}
-	let bundles = FontBundles::new(working_dir.join(font.name.to_string() + ".png"));
+	let mut bundles = FontBundles::new(working_dir.join(font.name.to_string() + ".png"));
 
 	// Create new font
 
+	// Read & parse source .ttf file
+	let data = fs::read(&font.path)
+		.nice_unwrap(format!("Unable to read font '{}'", font.path.display()));
+	let loaded = load_font(font, &data);
+
 	info!("Creating normal font");
-	initialize_font_bundle(&bundles.sd, font, 4, mod_info);
+	bundles.bytes_saved += initialize_font_bundle(&bundles.sd, font, &loaded, 4, output);
 
 	info!("Creating HD font");
-	initialize_font_bundle(&bundles.hd, font, 2, mod_info);
+	bundles.bytes_saved += initialize_font_bundle(&bundles.hd, font, &loaded, 2, output);
 
 	info!("Creating UHD font");
-	initialize_font_bundle(&bundles.uhd, font, 1, mod_info);
+	bundles.bytes_saved += initialize_font_bundle(&bundles.uhd, font, &loaded, 1, output);
 
 	done!("Built font {}", font.name.bright_yellow());
 	bundles
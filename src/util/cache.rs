@@ -5,14 +5,16 @@ use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
-use crate::mod_file::BitmapFont;
-use crate::spritesheet::SpriteSheet;
+use crate::mod_file::{BitmapFont, SoundResources, TextureOutput};
+use crate::spritesheet::{self, SpriteSheet};
 use crate::{warn, NiceUnwrap};
 
 #[derive(Serialize, Deserialize)]
 pub struct ResourceCache {
 	pub spritesheets: HashMap<String, PathBuf>,
 	pub fonts: HashMap<String, PathBuf>,
+	#[serde(default)]
+	pub sounds: HashMap<String, PathBuf>,
 }
 
 pub struct CacheBundle {
@@ -55,36 +57,70 @@ pub enum CacheBundleSource {
 	Directory(PathBuf),
 }
 
-fn hash_sheet(sheet: &SpriteSheet) -> String {
+fn hash_sheet(sheet: &SpriteSheet, output: &TextureOutput, animation_descriptor: bool) -> String {
 	let mut hashes: Vec<String> = sheet
 		.files
 		.iter()
-		.map(|x| sha256::digest_file(x).unwrap())
+		.map(|x| {
+			let hash = sha256::digest_file(&x.path).unwrap() + &format!("{:?}", x.size);
+
+			// Prebuilt sheets are only up-to-date if their texture is too
+			if x.path.extension().is_some_and(|ext| ext == "plist") {
+				if let Ok(prebuilt) = spritesheet::read_sheet_plist(&x.path) {
+					return hash + &sha256::try_digest(prebuilt.texture.as_path()).unwrap_or_default();
+				}
+			}
+			hash
+		})
 		.collect();
 	hashes.sort();
+	hashes.push(format!("{:?}", output));
+	// The descriptor is only written by builds that asked for it
+	if animation_descriptor {
+		hashes.push("animations".into());
+	}
 	sha256::digest(hashes.into_iter().collect::<String>())
 }
 
-fn hash_font(font: &BitmapFont) -> String {
+fn hash_font(font: &BitmapFont, output: &TextureOutput) -> String {
 	sha256::digest(format!(
-		"{}|{}|{}|{}",
+		"{}|{}|{}|{}|{}|{}|{:?}|{:?}",
 		font.size,
 		font.outline,
 		font.charset.clone().unwrap_or_default(),
-		sha256::digest_file(font.path.clone()).unwrap()
+		sha256::digest_file(font.path.clone()).unwrap(),
+		font.face_index,
+		font.instance.clone().unwrap_or_default(),
+		font.variations,
+		output
+	))
+}
+
+fn hash_sound(path: &Path, options: &SoundResources) -> String {
+	sha256::digest(format!(
+		"{}|{}|{:?}",
+		sha256::try_digest(path).unwrap(),
+		options.quality,
+		options.normalize
 	))
 }
 
 pub fn get_cache_bundle_from_dir(path: &Path) -> Option<CacheBundle> {
-	path.join("._cache")
+	get_cache_bundle_from_dirs(path, path)
+}
+
+/// Cache whose `._cache` file is kept in a different directory than the
+/// files it lists
+pub fn get_cache_bundle_from_dirs(cache_dir: &Path, files_dir: &Path) -> Option<CacheBundle> {
+	cache_dir.join("._cache")
 		.exists()
 		.then(|| {
 			let cache = ResourceCache::load(
-				fs::read_to_string(path.join("._cache")).nice_unwrap("Unable to read cache"),
+				fs::read_to_string(cache_dir.join("._cache")).nice_unwrap("Unable to read cache"),
 			);
 			Some(CacheBundle {
 				cache,
-				src: CacheBundleSource::Directory(path.to_path_buf()),
+				src: CacheBundleSource::Directory(files_dir.to_path_buf()),
 			})
 		})
 		.flatten()
@@ -131,6 +167,7 @@ impl ResourceCache {
 		ResourceCache {
 			spritesheets: HashMap::new(),
 			fonts: HashMap::new(),
+			sounds: HashMap::new(),
 		}
 	}
 
@@ -147,25 +184,47 @@ impl ResourceCache {
 		.unwrap()
 	}
 
-	pub fn add_sheet(&mut self, sheet: &SpriteSheet, path: PathBuf) {
+	pub fn add_sheet(
+		&mut self,
+		sheet: &SpriteSheet,
+		output: &TextureOutput,
+		animation_descriptor: bool,
+		path: PathBuf,
+	) {
 		if !path.is_relative() {
 			unreachable!("Contact  developers: {}", path.display());
 		}
-		self.spritesheets.insert(hash_sheet(sheet), path);
+		self.spritesheets.insert(hash_sheet(sheet, output, animation_descriptor), path);
 	}
 
-	pub fn add_font(&mut self, font: &BitmapFont, path: PathBuf) {
+	pub fn add_font(&mut self, font: &BitmapFont, output: &TextureOutput, path: PathBuf) {
 		if !path.is_relative() {
 			unreachable!("Contact  developers: {}", path.display());
 		}
-		self.fonts.insert(hash_font(font), path);
+		self.fonts.insert(hash_font(font, output), path);
+	}
+
+	pub fn add_sound(&mut self, sound: &Path, options: &SoundResources, path: PathBuf) {
+		if !path.is_relative() {
+			unreachable!("Contact  developers: {}", path.display());
+		}
+		self.sounds.insert(hash_sound(sound, options), path);
+	}
+
+	pub fn fetch_spritesheet_bundles(
+		&self,
+		sheet: &SpriteSheet,
+		output: &TextureOutput,
+		animation_descriptor: bool,
+	) -> Option<&Path> {
+		self.spritesheets.get(&hash_sheet(sheet, output, animation_descriptor)).map(|x| &**x)
 	}
 
-	pub fn fetch_spritesheet_bundles(&self, sheet: &SpriteSheet) -> Option<&Path> {
-		self.spritesheets.get(&hash_sheet(sheet)).map(|x| &**x)
+	pub fn fetch_font_bundles(&self, font: &BitmapFont, output: &TextureOutput) -> Option<&Path> {
+		self.fonts.get(&hash_font(font, output)).map(|x| &**x)
 	}
 
-	pub fn fetch_font_bundles(&self, font: &BitmapFont) -> Option<&Path> {
-		self.fonts.get(&hash_font(font)).map(|x| &**x)
+	pub fn fetch_sound(&self, sound: &Path, options: &SoundResources) -> Option<&Path> {
+		self.sounds.get(&hash_sound(sound, options)).map(|x| &**x)
 	}
 }
@@ -0,0 +1,122 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use path_absolutize::Absolutize;
+
+use crate::bmfont::FontBundles;
+use crate::mod_file::ModResources;
+use crate::spritesheet::{self, SheetBundles};
+use crate::NiceUnwrap;
+
+/// Every file that building the resources reads
+pub fn resource_inputs(resources: &ModResources) -> Vec<PathBuf> {
+	let mut inputs = Vec::new();
+	inputs.extend(resources.libraries.iter().cloned());
+	inputs.extend(resources.files.iter().cloned());
+	inputs.extend(resources.sprites.iter().map(|x| x.path.clone()));
+	for sheet in resources.spritesheets.values() {
+		inputs.extend(sheet.files.iter().map(|x| x.path.clone()));
+		// Prebuilt sheets also read the texture their plist points to
+		inputs.extend(spritesheet::prebuilt_textures(&sheet.files));
+	}
+	inputs.extend(resources.fonts.values().map(|x| x.path.clone()));
+	inputs.extend(resources.localization.languages.values().cloned());
+	inputs.extend(resources.shaders.iter().cloned());
+	inputs.extend(resources.sounds.files.iter().cloned());
+	inputs
+}
+
+/// Every file that building the resources writes into the output directory
+pub fn resource_outputs(resources: &ModResources, output_dir: &Path) -> Vec<PathBuf> {
+	let copied = |path: &PathBuf| output_dir.join(path.file_name().unwrap());
+	let output = &resources.texture_format;
+
+	let mut outputs = Vec::new();
+	for name in resources.spritesheets.keys() {
+		let bundles = SheetBundles::new(output_dir.join(name.to_string() + ".png"));
+		for bundle in [bundles.sd, bundles.hd, bundles.uhd] {
+			outputs.push(bundle.plist);
+			if output.png() {
+				outputs.push(bundle.png);
+			}
+			if output.pvr() {
+				outputs.push(bundle.pvr);
+			}
+		}
+		// Only written for sheets that have animations
+		let descriptor = output_dir.join(name.to_string() + ".animations.json");
+		if resources.animation_descriptors && descriptor.exists() {
+			outputs.push(descriptor);
+		}
+	}
+	for name in resources.fonts.keys() {
+		let bundles = FontBundles::new(output_dir.join(name.to_string() + ".png"));
+		for bundle in [bundles.sd, bundles.hd, bundles.uhd] {
+			outputs.push(bundle.fnt);
+			if output.png() {
+				outputs.push(bundle.png);
+			}
+			if output.pvr() {
+				outputs.push(bundle.pvr);
+			}
+		}
+	}
+	for sprite in &resources.sprites {
+		let base = sprite.path.file_stem().unwrap().to_str().unwrap();
+		for suffix in ["-uhd.png", "-hd.png", ".png"] {
+			outputs.push(output_dir.join(base.to_string() + suffix));
+		}
+	}
+	outputs.extend(resources.files.iter().map(copied));
+	outputs.extend(resources.libraries.iter().map(copied));
+	outputs.extend(resources.shaders.iter().map(copied));
+	for sound in &resources.sounds.files {
+		let transcoded = resources.sounds.transcode
+			&& sound.extension().is_some_and(|x| !x.eq_ignore_ascii_case("mp3"));
+		outputs.push(if transcoded { copied(&sound.with_extension("ogg")) } else { copied(sound) });
+	}
+	for lang in resources.localization.languages.keys() {
+		outputs.push(output_dir.join("lang").join(format!("{lang}.json")));
+	}
+	outputs
+}
+
+/// Escape a path the way Make and Ninja read depfiles
+fn escape(path: &Path) -> String {
+	let path = path.absolutize().map(|x| x.to_path_buf()).unwrap_or(path.to_path_buf());
+	let mut out = String::new();
+	for c in path.to_str().unwrap().chars() {
+		match c {
+			' ' | '#' => {
+				out.push('\\');
+				out.push(c);
+			}
+			'$' => out.push_str("$$"),
+			_ => out.push(c),
+		}
+	}
+	out
+}
+
+/// Write a Makefile-style depfile, in which every target depends on every
+/// input. Paths are made absolute so the depfile works from any directory
+pub fn write_depfile(path: &Path, inputs: &[PathBuf], targets: &[PathBuf]) {
+	let inputs = inputs.iter().map(|x| escape(x)).collect::<BTreeSet<_>>();
+	// The depfile itself is never a target, however its path was given
+	let targets = targets
+		.iter()
+		.map(|x| escape(x))
+		.filter(|x| *x != escape(path))
+		.collect::<BTreeSet<_>>();
+
+	let mut data = targets.into_iter().collect::<Vec<_>>().join(" \\\n  ");
+	data += ":";
+	for input in inputs {
+		data += " \\\n  ";
+		data += &input;
+	}
+	data += "\n";
+
+	fs::write(path, data).nice_unwrap("Unable to write depfile");
+}
@@ -0,0 +1,1000 @@
+use std::collections::HashMap;
+
+use crate::shader::{tokenize_line, Line, ShaderIssue, ShaderStage, Token, TokenKind};
+
+const DIRECTIVES: &[&str] = &[
+	"define", "undef", "if", "ifdef", "ifndef", "else", "elif", "endif",
+	"error", "pragma", "extension", "version", "line",
+];
+
+const KEYWORDS: &[&str] = &[
+	"attribute", "const", "uniform", "varying", "break", "continue", "do",
+	"for", "while", "if", "else", "in", "out", "inout", "float", "int", "void",
+	"bool", "true", "false", "lowp", "mediump", "highp", "precision",
+	"invariant", "discard", "return", "mat2", "mat3", "mat4", "vec2", "vec3",
+	"vec4", "ivec2", "ivec3", "ivec4", "bvec2", "bvec3", "bvec4", "sampler2D",
+	"samplerCube", "struct",
+];
+
+const TYPES: &[&str] = &[
+	"void", "bool", "int", "float", "vec2", "vec3", "vec4", "bvec2", "bvec3",
+	"bvec4", "ivec2", "ivec3", "ivec4", "mat2", "mat3", "mat4", "sampler2D",
+	"samplerCube",
+];
+
+const PRECISIONS: &[&str] = &["lowp", "mediump", "highp"];
+
+const BUILTIN_FUNCTIONS: &[&str] = &[
+	"radians", "degrees", "sin", "cos", "tan", "asin", "acos", "atan", "pow",
+	"exp", "log", "exp2", "log2", "sqrt", "inversesqrt", "abs", "sign",
+	"floor", "ceil", "fract", "mod", "min", "max", "clamp", "mix", "step",
+	"smoothstep", "length", "distance", "dot", "cross", "normalize",
+	"faceforward", "reflect", "refract", "matrixCompMult", "lessThan",
+	"lessThanEqual", "greaterThan", "greaterThanEqual", "equal", "notEqual",
+	"any", "all", "not", "texture2D", "texture2DProj", "texture2DLod",
+	"texture2DProjLod", "textureCube", "textureCubeLod",
+	// OES_standard_derivatives, which most devices have
+	"dFdx", "dFdy", "fwidth",
+];
+
+const BUILTIN_VARIABLES: &[&str] = &[
+	"gl_Position", "gl_PointSize", "gl_FragCoord", "gl_FrontFacing",
+	"gl_FragColor", "gl_FragData", "gl_PointCoord", "gl_DepthRange",
+	"gl_MaxVertexAttribs", "gl_MaxVertexUniformVectors", "gl_MaxVaryingVectors",
+	"gl_MaxVertexTextureImageUnits", "gl_MaxCombinedTextureImageUnits",
+	"gl_MaxTextureImageUnits", "gl_MaxFragmentUniformVectors",
+	"gl_MaxDrawBuffers", "__LINE__", "__FILE__", "__VERSION__",
+];
+
+const ASSIGNMENT_OPERATORS: &[&str] = &[
+	"=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "^=", "|=",
+];
+
+/// Binary operators from loosest to tightest. Reserved ones are parsed too,
+/// as they're reported on their own
+const BINARY_OPERATORS: &[&[&str]] = &[
+	&["||"], &["^^"], &["&&"], &["|"], &["^"], &["&"], &["==", "!="],
+	&["<", ">", "<=", ">="], &["<<", ">>"], &["+", "-"], &["*", "/", "%"],
+];
+
+fn issue(line: usize, message: impl Into<String>) -> ShaderIssue {
+	ShaderIssue { line, message: message.into() }
+}
+
+struct Macro<'a> {
+	/// None for object-like macros
+	params: Option<Vec<&'a str>>,
+	body: Vec<Token<'a>>,
+}
+
+/// An `#if` and its branches
+struct Conditional {
+	line: usize,
+	/// Whether the code around it is compiled
+	outer_active: bool,
+	/// Whether any branch so far was taken
+	taken: bool,
+	active: bool,
+	seen_else: bool,
+}
+
+pub struct Preprocessed<'a> {
+	/// Tokens that are compiled, as written
+	pub code: Vec<Token<'a>>,
+	/// Tokens that are compiled, with macros expanded
+	pub expanded: Vec<Token<'a>>,
+}
+
+struct Preprocessor<'a> {
+	macros: HashMap<&'a str, Macro<'a>>,
+	conditionals: Vec<Conditional>,
+	issues: Vec<ShaderIssue>,
+}
+
+impl<'a> Preprocessor<'a> {
+	fn active(&self) -> bool {
+		self.conditionals.last().is_none_or(|x| x.active)
+	}
+
+	/// Expand the macros in a run of tokens. Macros being expanded are
+	/// disabled so they can't expand into themselves
+	fn expand(&mut self, tokens: &[Token<'a>], disabled: &mut Vec<&'a str>) -> Vec<Token<'a>> {
+		let mut out = Vec::new();
+		let mut i = 0;
+		while i < tokens.len() {
+			let token = tokens[i];
+			i += 1;
+			let Some(mac) = self.macros.get(token.text).filter(|_| !disabled.contains(&token.text)) else {
+				out.push(token);
+				continue;
+			};
+			let at_use = |x: &Token<'a>| Token { line: token.line, ..*x };
+
+			let body = match &mac.params {
+				None => mac.body.iter().map(at_use).collect::<Vec<_>>(),
+				// Function-like macros are only expanded when called
+				Some(_) if tokens.get(i).is_none_or(|x| x.text != "(") => {
+					out.push(token);
+					continue;
+				}
+				Some(params) => {
+					let params = params.clone();
+					let body = mac.body.iter().map(at_use).collect::<Vec<_>>();
+
+					// Split the arguments on commas outside of parentheses
+					let mut args = vec![Vec::new()];
+					let mut depth = 0;
+					i += 1;
+					loop {
+						let Some(arg_token) = tokens.get(i) else {
+							self.issues.push(issue(token.line, format!("Unterminated call of macro '{}'", token.text)));
+							return out;
+						};
+						i += 1;
+						match arg_token.text {
+							")" if depth == 0 => break,
+							"," if depth == 0 => args.push(Vec::new()),
+							text => {
+								depth += (text == "(") as usize;
+								depth -= (text == ")") as usize;
+								args.last_mut().unwrap().push(*arg_token);
+							}
+						}
+					}
+					if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+						args.clear();
+					}
+					if args.len() != params.len() {
+						self.issues.push(issue(token.line, format!(
+							"Macro '{}' takes {} argument(s), but was given {}",
+							token.text, params.len(), args.len()
+						)));
+						continue;
+					}
+
+					// Arguments are expanded before they're substituted
+					let args = args.iter().map(|x| self.expand(x, disabled)).collect::<Vec<_>>();
+					body.iter()
+						.flat_map(|x| match params.iter().position(|p| *p == x.text) {
+							Some(index) => args[index].clone(),
+							None => vec![*x],
+						})
+						.collect()
+				}
+			};
+
+			disabled.push(token.text);
+			out.extend(self.expand(&body, disabled));
+			disabled.pop();
+		}
+		out
+	}
+
+	/// Evaluate the expression of an `#if` or `#elif`
+	fn evaluate(&mut self, tokens: &[Token<'a>], line: usize) -> bool {
+		// `defined` is resolved before macros are expanded
+		let mut resolved = Vec::new();
+		let mut i = 0;
+		while i < tokens.len() {
+			if tokens[i].text != "defined" {
+				resolved.push(tokens[i]);
+				i += 1;
+				continue;
+			}
+			let parens = tokens.get(i + 1).is_some_and(|x| x.text == "(");
+			let name = tokens.get(i + 1 + parens as usize);
+			if name.is_none_or(|x| x.kind != TokenKind::Ident)
+				|| (parens && tokens.get(i + 3).is_none_or(|x| x.text != ")"))
+			{
+				self.issues.push(issue(line, "Invalid use of 'defined'"));
+				return false;
+			}
+			let defined = self.macros.contains_key(name.unwrap().text);
+			resolved.push(Token { kind: TokenKind::Number, text: if defined { "1" } else { "0" }, line });
+			i += 2 + 2 * parens as usize;
+		}
+
+		let expanded = self.expand(&resolved, &mut Vec::new());
+		let mut eval = Evaluator { tokens: &expanded, pos: 0, line };
+		match eval.binary(0).and_then(|value| match eval.tokens.get(eval.pos) {
+			Some(x) => Err(format!("Unexpected '{}' in #if", x.text)),
+			None => Ok(value),
+		}) {
+			Ok(value) => value != 0,
+			Err(message) => {
+				self.issues.push(issue(line, message));
+				false
+			}
+		}
+	}
+
+	fn directive(&mut self, text: &'a str, line: usize, seen_code: bool) {
+		let tokens = match tokenize_line(&text[1..], line) {
+			Ok(tokens) => tokens,
+			Err(e) => {
+				self.issues.push(e);
+				return;
+			}
+		};
+		let Some(name) = tokens.first().map(|x| x.text) else {
+			return;
+		};
+		let args = &tokens[1..];
+		let active = self.active();
+
+		match name {
+			"if" | "ifdef" | "ifndef" => {
+				let value = active && match name {
+					"if" => self.evaluate(args, line),
+					_ => {
+						let Some(mac) = args.first().filter(|x| x.kind == TokenKind::Ident) else {
+							self.issues.push(issue(line, format!("#{name} needs a macro name")));
+							return self.conditionals.push(Conditional {
+								line, outer_active: active, taken: true, active: false, seen_else: false,
+							});
+						};
+						self.macros.contains_key(mac.text) == (name == "ifdef")
+					}
+				};
+				self.conditionals.push(Conditional {
+					line, outer_active: active, taken: value, active: value, seen_else: false,
+				});
+			}
+			"elif" | "else" => {
+				let Some(cond) = self.conditionals.last() else {
+					return self.issues.push(issue(line, format!("#{name} without #if")));
+				};
+				if cond.seen_else {
+					return self.issues.push(issue(line, format!("#{name} after #else")));
+				}
+				let value = cond.outer_active && !cond.taken && (name == "else" || self.evaluate(args, line));
+				let cond = self.conditionals.last_mut().unwrap();
+				cond.seen_else = name == "else";
+				cond.taken |= value;
+				cond.active = value;
+			}
+			"endif" => {
+				if self.conditionals.pop().is_none() {
+					self.issues.push(issue(line, "#endif without #if"));
+				}
+			}
+			// Everything else only matters in code that's compiled
+			_ if !active => {}
+			"define" => {
+				let Some(mac) = args.first().filter(|x| x.kind == TokenKind::Ident) else {
+					return self.issues.push(issue(line, "#define needs a macro name"));
+				};
+				if mac.text.starts_with("GL_") || mac.text.contains("__") {
+					return self.issues.push(issue(line, format!("Macro name '{}' is reserved", mac.text)));
+				}
+				// A parenthesis right after the name makes it function-like
+				let function_like = args.get(1).is_some_and(|x| {
+					x.text == "(" && x.text.as_ptr() as usize == mac.text.as_ptr() as usize + mac.text.len()
+				});
+				let (params, body) = if function_like {
+					let Some(close) = args.iter().position(|x| x.text == ")") else {
+						return self.issues.push(issue(line, format!("Unterminated parameters of macro '{}'", mac.text)));
+					};
+					let params = args[2..close].iter().filter(|x| x.text != ",").map(|x| x.text).collect();
+					(Some(params), args[close + 1..].to_vec())
+				} else {
+					(None, args[1..].to_vec())
+				};
+				self.macros.insert(mac.text, Macro { params, body });
+			}
+			"undef" => match args.first() {
+				Some(mac) if mac.text.starts_with("GL_") || mac.text.contains("__") => {
+					self.issues.push(issue(line, format!("Macro name '{}' is reserved", mac.text)));
+				}
+				Some(mac) => {
+					self.macros.remove(mac.text);
+				}
+				None => self.issues.push(issue(line, "#undef needs a macro name")),
+			},
+			"version" => {
+				if seen_code {
+					self.issues.push(issue(line, "#version must come before anything else"));
+				}
+				if let Some(version) = args.first().filter(|x| x.text != "100") {
+					self.issues.push(issue(line, format!(
+						"GLSL version {} is not supported, cocos2d uses GLSL ES 1.00 (#version 100)",
+						version.text
+					)));
+				}
+			}
+			"extension" => {
+				let valid = matches!(args, [name, colon, behavior]
+					if name.kind == TokenKind::Ident
+						&& colon.text == ":"
+						&& ["require", "enable", "warn", "disable"].contains(&behavior.text));
+				if !valid {
+					self.issues.push(issue(line, "Expected '#extension <name> : <behavior>'"));
+				}
+			}
+			"error" => self.issues.push(issue(line, format!("#error {}", text[1..].trim()[5..].trim()))),
+			"pragma" | "line" => {}
+			_ => self.issues.push(issue(line, format!("Unknown directive '#{name}'"))),
+		}
+	}
+}
+
+/// Run the preprocessor over the lines of a shader, leaving out code in
+/// branches that aren't taken and expanding macros
+pub fn preprocess<'a>(lines: &[Line<'a>], stage: ShaderStage, issues: &mut Vec<ShaderIssue>) -> Preprocessed<'a> {
+	let predefined = |value: &'static str| Macro {
+		params: None,
+		body: vec![Token { kind: TokenKind::Number, text: value, line: 0 }],
+	};
+	let mut pre = Preprocessor {
+		macros: HashMap::from([("GL_ES", predefined("1")), ("__VERSION__", predefined("100"))]),
+		conditionals: Vec::new(),
+		issues: Vec::new(),
+	};
+	if stage == ShaderStage::Fragment {
+		pre.macros.insert("GL_FRAGMENT_PRECISION_HIGH", predefined("1"));
+	}
+
+	let mut code = Vec::new();
+	let mut expanded = Vec::new();
+	// Macros only change on directives, so the code in between is expanded
+	// at once, which lets macro calls span lines
+	let mut pending = Vec::new();
+	for (i, line) in lines.iter().enumerate() {
+		match line {
+			Line::Code(tokens) => {
+				if pre.active() {
+					code.extend(tokens.iter().copied());
+					pending.extend(tokens.iter().copied());
+				}
+			}
+			Line::Directive(text) => {
+				expanded.extend(pre.expand(&pending, &mut Vec::new()));
+				pending.clear();
+				let name = text[1..].split_whitespace().next().unwrap_or_default();
+				if !name.is_empty() && !DIRECTIVES.contains(&name) && pre.active() {
+					pre.issues.push(issue(i + 1, format!("Unknown directive '#{name}'")));
+				} else {
+					pre.directive(text, i + 1, !code.is_empty());
+				}
+			}
+		}
+	}
+	expanded.extend(pre.expand(&pending, &mut Vec::new()));
+
+	for cond in &pre.conditionals {
+		pre.issues.push(issue(cond.line, "Unterminated #if"));
+	}
+	issues.append(&mut pre.issues);
+
+	Preprocessed { code, expanded }
+}
+
+/// Integer expression of an `#if`
+struct Evaluator<'t, 'a> {
+	tokens: &'t [Token<'a>],
+	pos: usize,
+	line: usize,
+}
+
+impl Evaluator<'_, '_> {
+	fn binary(&mut self, level: usize) -> Result<i64, String> {
+		let Some(ops) = BINARY_OPERATORS.get(level) else {
+			return self.unary();
+		};
+		let mut value = self.binary(level + 1)?;
+		while let Some(op) = self.tokens.get(self.pos).map(|x| x.text).filter(|x| ops.contains(x)) {
+			self.pos += 1;
+			let rhs = self.binary(level + 1)?;
+			value = match op {
+				"||" => (value != 0 || rhs != 0) as i64,
+				"^^" => ((value != 0) != (rhs != 0)) as i64,
+				"&&" => (value != 0 && rhs != 0) as i64,
+				"|" => value | rhs,
+				"^" => value ^ rhs,
+				"&" => value & rhs,
+				"==" => (value == rhs) as i64,
+				"!=" => (value != rhs) as i64,
+				"<" => (value < rhs) as i64,
+				">" => (value > rhs) as i64,
+				"<=" => (value <= rhs) as i64,
+				">=" => (value >= rhs) as i64,
+				"<<" => value.wrapping_shl(rhs as u32),
+				">>" => value.wrapping_shr(rhs as u32),
+				"+" => value.wrapping_add(rhs),
+				"-" => value.wrapping_sub(rhs),
+				"*" => value.wrapping_mul(rhs),
+				_ if rhs == 0 => return Err("Division by zero in #if".into()),
+				"/" => value.wrapping_div(rhs),
+				_ => value.wrapping_rem(rhs),
+			};
+		}
+		Ok(value)
+	}
+
+	fn unary(&mut self) -> Result<i64, String> {
+		let Some(token) = self.tokens.get(self.pos) else {
+			return Err("Expected a value in #if".into());
+		};
+		self.pos += 1;
+		match token.text {
+			"+" => self.unary(),
+			"-" => self.unary().map(|x| x.wrapping_neg()),
+			"~" => self.unary().map(|x| !x),
+			"!" => self.unary().map(|x| (x == 0) as i64),
+			"(" => {
+				let value = self.binary(0)?;
+				match self.tokens.get(self.pos) {
+					Some(x) if x.text == ")" => {
+						self.pos += 1;
+						Ok(value)
+					}
+					_ => Err("Expected ')' in #if".into()),
+				}
+			}
+			"__LINE__" => Ok(self.line as i64),
+			text if token.kind == TokenKind::Number => {
+				let parsed = if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+					i64::from_str_radix(hex, 16)
+				} else if text.len() > 1 && text.starts_with('0') {
+					i64::from_str_radix(&text[1..], 8)
+				} else {
+					text.parse()
+				};
+				parsed.map_err(|_| format!("'{text}' is not an integer"))
+			}
+			text if token.kind == TokenKind::Ident => Err(format!("'{text}' is not defined")),
+			text => Err(format!("Unexpected '{text}' in #if")),
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Symbol {
+	Type,
+	Variable,
+	Function,
+}
+
+type ParseResult = Result<(), ShaderIssue>;
+
+/// Recursive descent parser for the GLSL ES 1.00 grammar. Syntax errors stop
+/// it, while names that aren't declared are collected and parsing goes on.
+/// Types aren't checked
+struct Parser<'t, 'a> {
+	tokens: &'t [Token<'a>],
+	pos: usize,
+	stage: ShaderStage,
+	/// Names declared in each scope, with built-ins first and globals next
+	scopes: Vec<HashMap<&'a str, Symbol>>,
+	issues: Vec<ShaderIssue>,
+}
+
+impl<'a> Parser<'_, 'a> {
+	fn peek(&self) -> &'a str {
+		self.peek_at(0)
+	}
+
+	fn peek_at(&self, offset: usize) -> &'a str {
+		self.tokens.get(self.pos + offset).map(|x| x.text).unwrap_or_default()
+	}
+
+	fn line(&self) -> usize {
+		self.tokens
+			.get(self.pos)
+			.or(self.tokens.last())
+			.map(|x| x.line)
+			.unwrap_or(1)
+	}
+
+	fn eat(&mut self, text: &str) -> bool {
+		let found = self.pos < self.tokens.len() && self.peek() == text;
+		self.pos += found as usize;
+		found
+	}
+
+	fn unexpected(&self, expected: &str) -> ShaderIssue {
+		match self.tokens.get(self.pos) {
+			Some(token) => issue(token.line, format!("Expected {expected} but found '{}'", token.text)),
+			None => issue(self.line(), format!("Expected {expected} but reached the end")),
+		}
+	}
+
+	fn expect(&mut self, text: &str) -> ParseResult {
+		if self.eat(text) {
+			Ok(())
+		} else {
+			Err(self.unexpected(&format!("'{text}'")))
+		}
+	}
+
+	fn lookup(&self, name: &str) -> Option<Symbol> {
+		self.scopes.iter().rev().find_map(|x| x.get(name).copied())
+	}
+
+	fn declare(&mut self, name: &'a str, symbol: Symbol, line: usize) {
+		let scope = self.scopes.last_mut().unwrap();
+		match scope.insert(name, symbol) {
+			// Functions may be declared before they're defined, and overloaded
+			Some(Symbol::Function) if symbol == Symbol::Function => {}
+			Some(_) => self.issues.push(issue(line, format!("'{name}' is already declared"))),
+			None => {}
+		}
+	}
+
+	fn is_type(&self, name: &str) -> bool {
+		TYPES.contains(&name) || self.lookup(name) == Some(Symbol::Type)
+	}
+
+	/// Name of something being declared
+	fn identifier(&mut self) -> Result<&'a str, ShaderIssue> {
+		let Some(token) = self.tokens.get(self.pos).filter(|x| x.kind == TokenKind::Ident) else {
+			return Err(self.unexpected("a name"));
+		};
+		if KEYWORDS.contains(&token.text) {
+			return Err(self.unexpected("a name"));
+		}
+		if token.text.starts_with("gl_") {
+			self.issues.push(issue(token.line, format!("Names starting with 'gl_' are reserved, like '{}'", token.text)));
+		}
+		self.pos += 1;
+		Ok(token.text)
+	}
+
+	fn translation_unit(&mut self) -> ParseResult {
+		while self.pos < self.tokens.len() {
+			self.declaration(true)?;
+		}
+		Ok(())
+	}
+
+	/// Whether a statement starting here is a declaration, not an expression.
+	/// A type followed by a parenthesis is a constructor call, and an unknown
+	/// name followed by another name is most likely a misspelled type
+	fn starts_declaration(&self) -> bool {
+		let text = self.peek();
+		let unknown_type = self.lookup(text).is_none()
+			&& self.tokens.get(self.pos + 1).is_some_and(|x| {
+				x.kind == TokenKind::Ident && !KEYWORDS.contains(&x.text)
+			});
+		["const", "attribute", "varying", "uniform", "invariant", "precision", "struct"].contains(&text)
+			|| PRECISIONS.contains(&text)
+			|| (self.is_type(text) && self.peek_at(1) != "(")
+			|| unknown_type
+	}
+
+	fn declaration(&mut self, global: bool) -> ParseResult {
+		let line = self.line();
+
+		if self.eat("precision") {
+			if !PRECISIONS.contains(&self.peek()) {
+				return Err(self.unexpected("'lowp', 'mediump' or 'highp'"));
+			}
+			self.pos += 1;
+			if !["int", "float", "sampler2D", "samplerCube"].contains(&self.peek()) {
+				return Err(self.unexpected("'int', 'float' or a sampler type"));
+			}
+			self.pos += 1;
+			return self.expect(";");
+		}
+
+		// Redeclaration of an existing varying or built-in as invariant
+		if self.peek() == "invariant" && self.peek_at(1) != "varying" && !self.is_type(self.peek_at(1)) {
+			self.pos += 1;
+			loop {
+				let Some(&token) = self.tokens.get(self.pos).filter(|x| x.kind == TokenKind::Ident) else {
+					return Err(self.unexpected("a name"));
+				};
+				self.pos += 1;
+				if self.lookup(token.text).is_none() {
+					self.issues.push(issue(token.line, format!("'{}' is not declared", token.text)));
+				}
+				if !self.eat(",") {
+					return self.expect(";");
+				}
+			}
+		}
+
+		// Qualifiers
+		if self.eat("invariant") {
+			self.expect("varying")?;
+		}
+		if let Some(qualifier) = ["const", "attribute", "varying", "uniform"].into_iter().find(|x| self.peek() == *x) {
+			self.pos += 1;
+			if qualifier != "const" && !global {
+				self.issues.push(issue(line, format!("'{qualifier}' can only be used on global variables")));
+			}
+		}
+
+		self.type_specifier()?;
+		// Just a struct
+		if self.eat(";") {
+			return Ok(());
+		}
+
+		let mut name_line = self.line();
+		let mut name = self.identifier()?;
+		if self.peek() == "(" {
+			if !global {
+				return Err(issue(name_line, "Functions can only be declared outside of other functions"));
+			}
+			self.declare(name, Symbol::Function, name_line);
+			return self.function(name_line);
+		}
+
+		loop {
+			if self.eat("[") {
+				self.expression()?;
+				self.expect("]")?;
+				if self.peek() == "=" {
+					return Err(issue(self.line(), "Arrays can't be initialized in GLSL ES 1.00"));
+				}
+			} else if self.eat("=") {
+				self.assignment()?;
+			}
+			// A variable can be used after its initializer
+			self.declare(name, Symbol::Variable, name_line);
+
+			if !self.eat(",") {
+				return self.expect(";");
+			}
+			name_line = self.line();
+			name = self.identifier()?;
+		}
+	}
+
+	/// Rest of a function prototype or definition after its name
+	fn function(&mut self, line: usize) -> ParseResult {
+		self.expect("(")?;
+		let mut params = Vec::new();
+		if self.peek() == "void" && self.peek_at(1) == ")" {
+			self.pos += 1;
+		}
+		while !self.eat(")") {
+			if !params.is_empty() {
+				self.expect(",")?;
+			}
+			self.eat("const");
+			let _ = ["in", "out", "inout"].iter().any(|x| self.eat(x));
+			self.type_specifier()?;
+			let param_line = self.line();
+			if self.peek() != "," && self.peek() != ")" && self.peek() != "[" {
+				params.push((self.identifier()?, param_line));
+			}
+			if self.eat("[") {
+				self.expression()?;
+				self.expect("]")?;
+			}
+		}
+
+		if self.eat(";") {
+			return Ok(());
+		}
+		if self.peek() != "{" {
+			return Err(self.unexpected("'{' or ';'"));
+		}
+
+		// Parameters and the body share a scope
+		self.scopes.push(HashMap::new());
+		for (name, param_line) in params {
+			self.declare(name, Symbol::Variable, param_line);
+		}
+		self.pos += 1;
+		while !self.eat("}") {
+			if self.pos >= self.tokens.len() {
+				return Err(issue(line, "Unterminated function body"));
+			}
+			self.statement()?;
+		}
+		self.scopes.pop();
+		Ok(())
+	}
+
+	fn type_specifier(&mut self) -> ParseResult {
+		if PRECISIONS.contains(&self.peek()) {
+			self.pos += 1;
+		}
+		let text = self.peek();
+		if self.is_type(text) {
+			self.pos += 1;
+			return Ok(());
+		}
+		if text != "struct" {
+			return match self.tokens.get(self.pos) {
+				Some(token) if token.kind == TokenKind::Ident && !KEYWORDS.contains(&token.text) => {
+					Err(issue(token.line, format!("Unknown type '{}'", token.text)))
+				}
+				_ => Err(self.unexpected("a type")),
+			};
+		}
+
+		let line = self.line();
+		self.pos += 1;
+		let name = (self.peek() != "{").then(|| self.identifier()).transpose()?;
+		self.expect("{")?;
+		// Member names have their own scope
+		self.scopes.push(HashMap::new());
+		let mut members = 0;
+		while !self.eat("}") {
+			self.type_specifier()?;
+			loop {
+				let member_line = self.line();
+				let member = self.identifier()?;
+				self.declare(member, Symbol::Variable, member_line);
+				members += 1;
+				if self.eat("[") {
+					self.expression()?;
+					self.expect("]")?;
+				}
+				if !self.eat(",") {
+					break;
+				}
+			}
+			self.expect(";")?;
+		}
+		self.scopes.pop();
+		if members == 0 {
+			self.issues.push(issue(line, "Structs need at least one member"));
+		}
+		if let Some(name) = name {
+			self.declare(name, Symbol::Type, line);
+		}
+		Ok(())
+	}
+
+	/// A statement that gets its own scope, like the body of an if
+	fn scoped_statement(&mut self) -> ParseResult {
+		self.scopes.push(HashMap::new());
+		let result = self.statement();
+		self.scopes.pop();
+		result
+	}
+
+	fn statement(&mut self) -> ParseResult {
+		let line = self.line();
+		match self.peek() {
+			"{" => {
+				self.pos += 1;
+				self.scopes.push(HashMap::new());
+				while !self.eat("}") {
+					if self.pos >= self.tokens.len() {
+						return Err(issue(line, "Unclosed '{'"));
+					}
+					self.statement()?;
+				}
+				self.scopes.pop();
+			}
+			"if" => {
+				self.pos += 1;
+				self.expect("(")?;
+				self.expression()?;
+				self.expect(")")?;
+				self.scoped_statement()?;
+				if self.eat("else") {
+					self.scoped_statement()?;
+				}
+			}
+			"while" => {
+				self.pos += 1;
+				self.expect("(")?;
+				self.scopes.push(HashMap::new());
+				self.condition()?;
+				self.expect(")")?;
+				self.statement()?;
+				self.scopes.pop();
+			}
+			"do" => {
+				self.pos += 1;
+				self.scoped_statement()?;
+				self.expect("while")?;
+				self.expect("(")?;
+				self.expression()?;
+				self.expect(")")?;
+				self.expect(";")?;
+			}
+			"for" => {
+				self.pos += 1;
+				self.expect("(")?;
+				self.scopes.push(HashMap::new());
+				if self.starts_declaration() {
+					self.declaration(false)?;
+				} else if !self.eat(";") {
+					self.expression()?;
+					self.expect(";")?;
+				}
+				if !self.eat(";") {
+					self.condition()?;
+					self.expect(";")?;
+				}
+				if !self.eat(")") {
+					self.expression()?;
+					self.expect(")")?;
+				}
+				self.statement()?;
+				self.scopes.pop();
+			}
+			"continue" | "break" => {
+				self.pos += 1;
+				self.expect(";")?;
+			}
+			"discard" => {
+				if self.stage == ShaderStage::Vertex {
+					self.issues.push(issue(line, "'discard' can only be used in fragment shaders"));
+				}
+				self.pos += 1;
+				self.expect(";")?;
+			}
+			"return" => {
+				self.pos += 1;
+				if !self.eat(";") {
+					self.expression()?;
+					self.expect(";")?;
+				}
+			}
+			";" => self.pos += 1,
+			_ if self.starts_declaration() => self.declaration(false)?,
+			_ => {
+				self.expression()?;
+				self.expect(";")?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Condition of a loop, which may declare a variable
+	fn condition(&mut self) -> ParseResult {
+		if !self.starts_declaration() {
+			return self.expression();
+		}
+		self.type_specifier()?;
+		let line = self.line();
+		let name = self.identifier()?;
+		self.expect("=")?;
+		self.assignment()?;
+		self.declare(name, Symbol::Variable, line);
+		Ok(())
+	}
+
+	fn expression(&mut self) -> ParseResult {
+		self.assignment()?;
+		while self.eat(",") {
+			self.assignment()?;
+		}
+		Ok(())
+	}
+
+	fn assignment(&mut self) -> ParseResult {
+		self.conditional()?;
+		if ASSIGNMENT_OPERATORS.contains(&self.peek()) {
+			self.pos += 1;
+			self.assignment()?;
+		}
+		Ok(())
+	}
+
+	fn conditional(&mut self) -> ParseResult {
+		self.binary(0)?;
+		if self.eat("?") {
+			self.expression()?;
+			self.expect(":")?;
+			self.assignment()?;
+		}
+		Ok(())
+	}
+
+	fn binary(&mut self, level: usize) -> ParseResult {
+		let Some(ops) = BINARY_OPERATORS.get(level) else {
+			return self.unary();
+		};
+		self.binary(level + 1)?;
+		while ops.contains(&self.peek()) {
+			self.pos += 1;
+			self.binary(level + 1)?;
+		}
+		Ok(())
+	}
+
+	fn unary(&mut self) -> ParseResult {
+		if ["++", "--", "+", "-", "!", "~"].contains(&self.peek()) {
+			self.pos += 1;
+			return self.unary();
+		}
+		self.primary()?;
+		loop {
+			match self.peek() {
+				"[" => {
+					self.pos += 1;
+					self.expression()?;
+					self.expect("]")?;
+				}
+				// Fields and swizzles aren't checked
+				"." => {
+					self.pos += 1;
+					if self.tokens.get(self.pos).is_none_or(|x| x.kind != TokenKind::Ident) {
+						return Err(self.unexpected("a field name"));
+					}
+					self.pos += 1;
+				}
+				"++" | "--" => self.pos += 1,
+				_ => return Ok(()),
+			}
+		}
+	}
+
+	fn primary(&mut self) -> ParseResult {
+		let Some(&token) = self.tokens.get(self.pos) else {
+			return Err(self.unexpected("an expression"));
+		};
+		match token.kind {
+			TokenKind::Number => self.pos += 1,
+			TokenKind::Ident if token.text == "true" || token.text == "false" => self.pos += 1,
+			// Constructors
+			TokenKind::Ident if self.is_type(token.text) => {
+				self.pos += 1;
+				self.expect("(")?;
+				self.arguments()?;
+			}
+			TokenKind::Ident if KEYWORDS.contains(&token.text) => return Err(self.unexpected("an expression")),
+			TokenKind::Ident => {
+				self.pos += 1;
+				let call = self.eat("(");
+				match (self.lookup(token.text), call) {
+					(None, _) => self.issues.push(issue(token.line, format!("'{}' is not declared", token.text))),
+					(Some(Symbol::Function), false) => {
+						self.issues.push(issue(token.line, format!("Function '{}' is used like a variable", token.text)));
+					}
+					(Some(Symbol::Variable), true) => {
+						self.issues.push(issue(token.line, format!("'{}' is not a function", token.text)));
+					}
+					_ => {}
+				}
+				if call {
+					self.arguments()?;
+				}
+			}
+			TokenKind::Punct if token.text == "(" => {
+				self.pos += 1;
+				self.expression()?;
+				self.expect(")")?;
+			}
+			TokenKind::Punct => return Err(self.unexpected("an expression")),
+		}
+		Ok(())
+	}
+
+	/// Arguments of a call, after its opening parenthesis
+	fn arguments(&mut self) -> ParseResult {
+		if self.peek() == "void" && self.peek_at(1) == ")" {
+			self.pos += 1;
+		}
+		if self.eat(")") {
+			return Ok(());
+		}
+		loop {
+			self.assignment()?;
+			if !self.eat(",") {
+				return self.expect(")");
+			}
+		}
+	}
+}
+
+/// Parse preprocessed tokens as a GLSL ES 1.00 translation unit. Returns the
+/// first syntax error along with any names used without being declared
+pub fn parse(tokens: &[Token], stage: ShaderStage, predeclared: &[&'static str]) -> Vec<ShaderIssue> {
+	let builtins = BUILTIN_FUNCTIONS
+		.iter()
+		.map(|x| (*x, Symbol::Function))
+		.chain(BUILTIN_VARIABLES.iter().chain(predeclared).map(|x| (*x, Symbol::Variable)))
+		.collect();
+	let mut parser = Parser {
+		tokens,
+		pos: 0,
+		stage,
+		scopes: vec![builtins, HashMap::new()],
+		issues: Vec::new(),
+	};
+	if let Err(e) = parser.translation_unit() {
+		parser.issues.push(e);
+	}
+	parser.issues
+}
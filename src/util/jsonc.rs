@@ -0,0 +1,535 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Turn JSON with comments and trailing commas into plain JSON. Comments and
+/// trailing commas are replaced with spaces of the same byte length, so
+/// offsets, line and column numbers still point into the original text
+pub fn strip(text: &str) -> String {
+	blank_out(text, true)
+}
+
+/// Replace comments, and trailing commas if asked to, with spaces
+fn blank_out(text: &str, trailing_commas: bool) -> String {
+	let chars = text.chars().collect::<Vec<_>>();
+	let mut blank = vec![false; chars.len()];
+
+	// Blank out comments
+	let mut i = 0;
+	let mut in_string = false;
+	while i < chars.len() {
+		let c = chars[i];
+		if in_string {
+			match c {
+				'\\' => i += 1,
+				'"' => in_string = false,
+				_ => {}
+			}
+			i += 1;
+			continue;
+		}
+		match (c, chars.get(i + 1)) {
+			('"', _) => in_string = true,
+			('/', Some('/')) => {
+				while i < chars.len() && chars[i] != '\n' {
+					blank[i] = true;
+					i += 1;
+				}
+				continue;
+			}
+			('/', Some('*')) => {
+				let start = i;
+				i += 2;
+				while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+					i += 1;
+				}
+				i = (i + 2).min(chars.len());
+				blank[start..i].iter_mut().for_each(|x| *x = true);
+				continue;
+			}
+			_ => {}
+		}
+		i += 1;
+	}
+
+	// Blank out commas followed only by whitespace before a closing bracket
+	let mut in_string = false;
+	let mut i = 0;
+	while trailing_commas && i < chars.len() {
+		let c = chars[i];
+		if blank[i] {
+			i += 1;
+			continue;
+		}
+		if in_string {
+			match c {
+				'\\' => i += 1,
+				'"' => in_string = false,
+				_ => {}
+			}
+		} else if c == '"' {
+			in_string = true;
+		} else if c == ',' {
+			let next = (i + 1..chars.len()).find(|x| !blank[*x] && !chars[*x].is_whitespace());
+			if next.is_some_and(|x| matches!(chars[x], '}' | ']')) {
+				blank[i] = true;
+			}
+		}
+		i += 1;
+	}
+
+	let mut out = String::with_capacity(text.len());
+	for (c, blank) in chars.into_iter().zip(blank) {
+		// Keep newlines so line numbers stay the same
+		if blank && c != '\n' && c != '\r' {
+			out.extend(std::iter::repeat_n(' ', c.len_utf8()));
+		} else {
+			out.push(c);
+		}
+	}
+	out
+}
+
+/// Parse JSON that may have comments and trailing commas
+pub fn from_str<T: serde::de::DeserializeOwned>(text: &str) -> serde_json::Result<T> {
+	serde_json::from_str(&strip(text))
+}
+
+fn to_string_indented<T: Serialize>(value: &T, indent: &str) -> String {
+	let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+	let mut ser = serde_json::Serializer::with_formatter(Vec::new(), formatter);
+	value.serialize(&mut ser).unwrap();
+	String::from_utf8(ser.into_inner()).unwrap()
+}
+
+/// Rewrite JSON that may have comments and trailing commas as plain JSON,
+/// indented with tabs
+pub fn to_strict_json(text: &str) -> serde_json::Result<String> {
+	Ok(to_string_indented(&from_str::<Value>(text)?, "\t"))
+}
+
+/// A JSON value along with where it is in the text
+pub struct Node {
+	/// Byte range of the value
+	pub start: usize,
+	pub end: usize,
+	pub line: usize,
+	pub column: usize,
+	pub kind: NodeKind,
+}
+
+pub struct Member {
+	pub key: String,
+	/// Byte range, line and column of the key
+	pub start: usize,
+	pub key_end: usize,
+	pub line: usize,
+	pub column: usize,
+	pub value: Node,
+}
+
+pub enum NodeKind {
+	Null,
+	Bool,
+	Number(f64),
+	String(String),
+	Array(Vec<Node>),
+	Object(Vec<Member>),
+}
+
+impl NodeKind {
+	pub fn type_name(&self) -> &'static str {
+		match self {
+			NodeKind::Null => "null",
+			NodeKind::Bool => "boolean",
+			NodeKind::Number(_) => "number",
+			NodeKind::String(_) => "string",
+			NodeKind::Array(_) => "array",
+			NodeKind::Object(_) => "object",
+		}
+	}
+}
+
+/// Minimal JSON parser that keeps track of positions. Only ever used on
+/// text serde_json has already accepted, so it doesn't report errors
+struct SpanParser<'a> {
+	text: &'a str,
+	pos: usize,
+	line: usize,
+	column: usize,
+}
+
+impl<'a> SpanParser<'a> {
+	fn peek(&self) -> Option<char> {
+		self.text[self.pos..].chars().next()
+	}
+
+	fn bump(&mut self) -> Option<char> {
+		let c = self.peek()?;
+		self.pos += c.len_utf8();
+		if c == '\n' {
+			self.line += 1;
+			self.column = 1;
+		} else {
+			self.column += 1;
+		}
+		Some(c)
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.peek().is_some_and(|c| c.is_whitespace()) {
+			self.bump();
+		}
+	}
+
+	fn parse_string(&mut self) -> String {
+		let start = self.pos;
+		self.bump();
+		while let Some(c) = self.bump() {
+			match c {
+				'\\' => {
+					self.bump();
+				}
+				'"' => break,
+				_ => {}
+			}
+		}
+		serde_json::from_str(&self.text[start..self.pos]).unwrap_or_default()
+	}
+
+	fn parse_value(&mut self) -> Node {
+		self.skip_whitespace();
+		let (start, line, column) = (self.pos, self.line, self.column);
+
+		let kind = match self.peek() {
+			Some('{') => {
+				self.bump();
+				let mut members = Vec::new();
+				loop {
+					self.skip_whitespace();
+					match self.peek() {
+						Some('"') => {
+							let (start, line, column) = (self.pos, self.line, self.column);
+							let key = self.parse_string();
+							let key_end = self.pos;
+							self.skip_whitespace();
+							self.bump();
+							members.push(Member {
+								key,
+								start,
+								key_end,
+								line,
+								column,
+								value: self.parse_value(),
+							});
+						}
+						Some(',') => {
+							self.bump();
+						}
+						_ => {
+							self.bump();
+							break;
+						}
+					}
+				}
+				NodeKind::Object(members)
+			}
+			Some('[') => {
+				self.bump();
+				let mut items = Vec::new();
+				loop {
+					self.skip_whitespace();
+					match self.peek() {
+						Some(',') => {
+							self.bump();
+						}
+						Some(']') | None => {
+							self.bump();
+							break;
+						}
+						_ => items.push(self.parse_value()),
+					}
+				}
+				NodeKind::Array(items)
+			}
+			Some('"') => NodeKind::String(self.parse_string()),
+			_ => {
+				let start = self.pos;
+				while self.peek().is_some_and(|c| !matches!(c, ',' | ']' | '}') && !c.is_whitespace()) {
+					self.bump();
+				}
+				match &self.text[start..self.pos] {
+					"null" => NodeKind::Null,
+					"true" | "false" => NodeKind::Bool,
+					number => NodeKind::Number(number.parse().unwrap_or_default()),
+				}
+			}
+		};
+
+		Node {
+			start,
+			end: self.pos,
+			line,
+			column,
+			kind,
+		}
+	}
+}
+
+/// Parse JSON that may have comments and trailing commas into nodes that
+/// know where they are. The text has to be valid, see [`from_str`]
+pub fn parse_spanned(text: &str) -> Node {
+	let text = strip(text);
+	SpanParser {
+		text: &text,
+		pos: 0,
+		line: 1,
+		column: 1,
+	}
+	.parse_value()
+}
+
+/// Find the node at a path of object keys and array indices
+pub fn find<'a>(mut node: &'a Node, path: &[&str]) -> Option<&'a Node> {
+	for key in path {
+		node = match &node.kind {
+			NodeKind::Object(members) => &members.iter().find(|x| x.key == *key)?.value,
+			NodeKind::Array(items) => items.get(key.parse::<usize>().ok()?)?,
+			_ => return None,
+		};
+	}
+	Some(node)
+}
+
+/// Edits JSON text in place, keeping the formatting, comments and key order
+/// of everything that isn't changed
+pub struct JsonEditor {
+	text: String,
+	indent: String,
+}
+
+impl JsonEditor {
+	pub fn new(text: String) -> serde_json::Result<Self> {
+		from_str::<Value>(&text)?;
+
+		// Match the indentation of the first indented line
+		let indent = text
+			.lines()
+			.map(|x| &x[..x.len() - x.trim_start().len()])
+			.find(|x| !x.is_empty())
+			.map(|x| if x.starts_with('\t') { "\t".to_string() } else { x.to_string() })
+			.unwrap_or("\t".into());
+
+		Ok(JsonEditor { text, indent })
+	}
+
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	pub fn get(&self, path: &[&str]) -> Option<Value> {
+		let root = parse_spanned(&self.text);
+		let node = find(&root, path)?;
+		serde_json::from_str(&strip(&self.text[node.start..node.end])).ok()
+	}
+
+	/// Indentation of the line that the byte offset is on
+	fn line_indent(&self, pos: usize) -> String {
+		let line_start = self.text[..pos].rfind('\n').map(|x| x + 1).unwrap_or(0);
+		let line = &self.text[line_start..];
+		line[..line.len() - line.trim_start().len()].to_string()
+	}
+
+	/// Serialize a value to be placed on a line indented with `base`
+	fn serialize(&self, value: &Value, base: &str) -> String {
+		to_string_indented(value, &self.indent).replace('\n', &format!("\n{base}"))
+	}
+
+	/// Insert an entry into an object or array after its last entry, with
+	/// the key if it's an object
+	fn insert_entry(&mut self, container: &Node, entries: &[(usize, usize)], key: Option<&str>, value: &Value) {
+		let key = key.map(|x| serde_json::to_string(x).unwrap() + ": ").unwrap_or_default();
+		let entry = |indent: &str| {
+			if indent.is_empty() {
+				key.clone() + &serde_json::to_string(value).unwrap()
+			} else {
+				key.clone() + &self.serialize(value, indent)
+			}
+		};
+
+		let (open, close) = match container.kind {
+			NodeKind::Object(_) => ('{', '}'),
+			_ => ('[', ']'),
+		};
+
+		let Some(&(first_start, _)) = entries.first() else {
+			let base = self.line_indent(container.start);
+			let inner = format!("{base}{}", self.indent);
+			let text = format!("{open}\n{inner}{}\n{base}{close}", entry(&inner));
+			self.text.replace_range(container.start..container.end, &text);
+			return;
+		};
+
+		let (last_start, last_end) = *entries.last().unwrap();
+		// Keep objects and arrays written on a single line that way
+		if self.text[container.start..first_start].contains('\n') {
+			let indent = self.line_indent(last_start);
+			let text = format!(",\n{indent}{}", entry(&indent));
+			self.text.insert_str(last_end, &text);
+		} else {
+			let text = format!(", {}", entry(""));
+			self.text.insert_str(last_end, &text);
+		}
+	}
+
+	/// Set the value at a path, adding it to the end of its parent object if
+	/// it doesn't exist yet
+	pub fn set(&mut self, path: &[&str], value: &Value) -> Result<(), String> {
+		let root = parse_spanned(&self.text);
+
+		if let Some(node) = find(&root, path) {
+			let base = self.line_indent(node.start);
+			let text = self.serialize(value, &base);
+			self.text.replace_range(node.start..node.end, &text);
+			return Ok(());
+		}
+
+		let (key, parent_path) = path.split_last().ok_or("Can't replace the root")?;
+		let parent = find(&root, parent_path).ok_or_else(|| format!("'{}' doesn't exist", parent_path.join(".")))?;
+		let NodeKind::Object(members) = &parent.kind else {
+			return Err(format!("'{}' is not an object", parent_path.join(".")));
+		};
+
+		let entries = members.iter().map(|x| (x.start, x.value.end)).collect::<Vec<_>>();
+		self.insert_entry(parent, &entries, Some(key), value);
+		Ok(())
+	}
+
+	/// Add a value to the end of the array at a path, creating the array if
+	/// it doesn't exist yet
+	pub fn push(&mut self, path: &[&str], value: &Value) -> Result<(), String> {
+		let root = parse_spanned(&self.text);
+
+		let Some(array) = find(&root, path) else {
+			return self.set(path, &Value::Array(vec![value.clone()]));
+		};
+		let NodeKind::Array(items) = &array.kind else {
+			return Err(format!("'{}' is not an array", path.join(".")));
+		};
+
+		let entries = items.iter().map(|x| (x.start, x.end)).collect::<Vec<_>>();
+		self.insert_entry(array, &entries, None, value);
+		Ok(())
+	}
+
+	/// Rename the key of the value at a path, keeping it where it is.
+	/// Returns whether the key existed
+	pub fn rename(&mut self, path: &[&str], new_key: &str) -> bool {
+		let root = parse_spanned(&self.text);
+
+		let Some((key, parent_path)) = path.split_last() else {
+			return false;
+		};
+		let Some(NodeKind::Object(members)) = find(&root, parent_path).map(|x| &x.kind) else {
+			return false;
+		};
+		let Some(member) = members.iter().find(|x| x.key == *key) else {
+			return false;
+		};
+
+		self.text.replace_range(member.start..member.key_end, &serde_json::to_string(new_key).unwrap());
+		true
+	}
+
+	/// Remove the value at a path along with its key and separating comma.
+	/// Comments around it are kept. Returns whether there was anything to
+	/// remove
+	pub fn remove(&mut self, path: &[&str]) -> bool {
+		let root = parse_spanned(&self.text);
+
+		let Some((key, parent_path)) = path.split_last() else {
+			return false;
+		};
+		let Some(parent) = find(&root, parent_path) else {
+			return false;
+		};
+
+		let (entries, index) = match &parent.kind {
+			NodeKind::Object(members) => (
+				members.iter().map(|x| (x.start, x.value.end)).collect::<Vec<_>>(),
+				members.iter().position(|x| x.key == *key),
+			),
+			NodeKind::Array(items) => (
+				items.iter().map(|x| (x.start, x.end)).collect::<Vec<_>>(),
+				key.parse::<usize>().ok().filter(|x| *x < items.len()),
+			),
+			_ => return false,
+		};
+		let Some(index) = index else {
+			return false;
+		};
+
+		// Commas are found in the text without comments, as those may have
+		// commas too
+		let code = blank_out(&self.text, false);
+		let comma_after = |pos: usize| {
+			let next = pos + code[pos..].len() - code[pos..].trim_start().len();
+			code[next..].starts_with(',').then_some(next)
+		};
+		// Whitespace, but not comments, right before or after a position
+		let space_before = |pos: usize| self.text[..pos].trim_end().len();
+		let space_after = |pos: usize| pos + self.text[pos..].len() - self.text[pos..].trim_start().len();
+
+		let (start, end) = entries[index];
+		let comma = comma_after(end);
+		let mut ranges = Vec::new();
+
+		if index + 1 < entries.len() {
+			// Up to the start of the line of the next entry or comment, which
+			// takes the place of this one
+			let comma = comma.unwrap();
+			ranges.push(start..end);
+			ranges.push(comma..space_after(comma + 1));
+		} else {
+			// The line this was on goes away unless a comment follows on it,
+			// and the entry before becomes the last one. A trailing comma of
+			// this one can stay on it instead
+			let line_end = end + self.text[end..].find('\n').unwrap_or(self.text.len() - end);
+			let commented = code[end..line_end].trim() != self.text[end..line_end].trim();
+			let inline_space_after = |pos: usize| {
+				pos + self.text[pos..].len() - self.text[pos..].trim_start_matches([' ', '\t']).len()
+			};
+			if commented {
+				ranges.push(start..inline_space_after(end));
+			} else {
+				ranges.push(space_before(start)..end);
+			}
+			match comma {
+				Some(comma) if commented => ranges.push(comma..inline_space_after(comma + 1)),
+				Some(comma) => ranges.push(comma..comma + 1),
+				None if index > 0 => {
+					let prev = comma_after(entries[index - 1].1).unwrap();
+					ranges.push(prev..prev + 1);
+				}
+				None => {}
+			}
+		}
+
+		// The ranges don't overlap, so they can be removed from the back
+		ranges.sort_by_key(|x| x.start);
+		for range in ranges.into_iter().rev() {
+			self.text.replace_range(range, "");
+		}
+
+		// Write a container that's left with nothing, not even comments, as
+		// `{}` or `[]`
+		if entries.len() == 1 {
+			let root = parse_spanned(&self.text);
+			let parent = find(&root, parent_path).unwrap();
+			if self.text[parent.start + 1..parent.end - 1].trim().is_empty() {
+				let empty = if matches!(parent.kind, NodeKind::Object(_)) { "{}" } else { "[]" };
+				self.text.replace_range(parent.start..parent.end, empty);
+			}
+		}
+		true
+	}
+}
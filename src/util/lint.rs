@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+
+use crate::mod_file::ModResources;
+use crate::spritesheet::{self, SpriteFile};
+
+/// Largest UHD sprite dimension that's safe to load on every platform
+const MAX_SPRITE_SIZE: u32 = 2048;
+
+/// Sprites whose visible content covers less than this fraction of their
+/// area should be trimmed
+const MIN_CONTENT_RATIO: f64 = 0.5;
+
+pub struct LintIssue {
+	pub path: PathBuf,
+	pub message: String,
+}
+
+impl Display for LintIssue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}: {}", self.path.display(), self.message)
+	}
+}
+
+/// Bounding box of the pixels that aren't fully transparent, as
+/// `(width, height)`, or None if the image is fully transparent
+fn content_size(img: &RgbaImage) -> Option<(u32, u32)> {
+	let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+	for (x, y, pixel) in img.enumerate_pixels() {
+		if pixel[3] != 0 {
+			min_x = min_x.min(x);
+			min_y = min_y.min(y);
+			max_x = max_x.max(x);
+			max_y = max_y.max(y);
+		}
+	}
+	(min_x <= max_x).then(|| (max_x - min_x + 1, max_y - min_y + 1))
+}
+
+fn lint_image(
+	path: &Path,
+	img: &RgbaImage,
+	check_padding: bool,
+	issues: &mut Vec<LintIssue>,
+) {
+	let mut issue = |message: String| issues.push(LintIssue { path: path.to_path_buf(), message });
+
+	let (width, height) = img.dimensions();
+	if width % 4 != 0 || height % 4 != 0 {
+		issue(format!(
+			"Size {width}x{height} is not divisible by 4, so the HD and SD \
+			versions will be rounded inconsistently"
+		));
+	}
+	if width > MAX_SPRITE_SIZE || height > MAX_SPRITE_SIZE {
+		issue(format!(
+			"Size {width}x{height} is larger than {MAX_SPRITE_SIZE}x{MAX_SPRITE_SIZE}"
+		));
+	}
+
+	match content_size(img) {
+		None => issue("Image is fully transparent".into()),
+		Some((content_width, content_height)) if check_padding => {
+			let ratio = (content_width * content_height) as f64 / (width * height) as f64;
+			if ratio < MIN_CONTENT_RATIO {
+				issue(format!(
+					"Visible content is only {content_width}x{content_height} of \
+					{width}x{height}, consider trimming the transparent padding"
+				));
+			}
+		}
+		_ => {}
+	}
+}
+
+fn lint_sprite_file(file: &SpriteFile, check_padding: bool, issues: &mut Vec<LintIssue>) {
+	let path = &file.path;
+
+	// Prebuilt sheets are someone else's art
+	if spritesheet::is_plist(path) {
+		return;
+	}
+
+	if spritesheet::is_svg(path) {
+		// Only the size hint decides how SVGs are rasterized
+		if let Some((width, height)) = file.size {
+			if width % 4 != 0 || height % 4 != 0 {
+				issues.push(LintIssue {
+					path: path.clone(),
+					message: format!("Size hint {width}x{height} is not divisible by 4"),
+				});
+			}
+		}
+		return;
+	}
+
+	if let Some(frames) = spritesheet::read_animation(path) {
+		// All frames share the same canvas, so only check the first one
+		lint_image(path, &frames[0].image, check_padding, issues);
+	} else {
+		lint_image(path, &spritesheet::read_to_image(path), check_padding, issues);
+	}
+}
+
+/// Check a mod's sprites and spritesheets for problems that don't stop them
+/// from being built but likely look wrong in game
+pub fn lint_resources(resources: &ModResources) -> Vec<LintIssue> {
+	let mut issues = Vec::new();
+
+	// Spritesheets are trimmed when packed, so padding only matters for
+	// standalone sprites
+	for file in &resources.sprites {
+		lint_sprite_file(file, true, &mut issues);
+	}
+
+	let mut sheet_files = Vec::new();
+	for sheet in resources.spritesheets.values() {
+		let textures = spritesheet::prebuilt_textures(&sheet.files);
+		sheet_files.extend(sheet.files.iter().filter(|x| !textures.contains(&x.path)));
+	}
+	for file in &sheet_files {
+		lint_sprite_file(file, false, &mut issues);
+	}
+
+	// Sprites are referred to by file stem, so those have to be unique
+	let mut stems: BTreeMap<String, Vec<&Path>> = BTreeMap::new();
+	let all_files = resources
+		.sprites
+		.iter()
+		.chain(sheet_files)
+		.filter(|x| !spritesheet::is_plist(&x.path));
+	for file in all_files {
+		let stem = file.path.file_stem().unwrap().to_string_lossy().to_string();
+		stems.entry(stem).or_default().push(&file.path);
+	}
+
+	for (stem, paths) in &stems {
+		if !stem.is_ascii() {
+			issues.push(LintIssue {
+				path: paths[0].to_path_buf(),
+				message: format!("Name '{stem}' contains non-ASCII characters"),
+			});
+		}
+		if paths.len() > 1 {
+			issues.push(LintIssue {
+				path: paths[0].to_path_buf(),
+				message: format!(
+					"Name '{stem}' is also used by {}",
+					paths[1..]
+						.iter()
+						.map(|x| format!("'{}'", x.display()))
+						.collect::<Vec<_>>()
+						.join(", ")
+				),
+			});
+		}
+	}
+
+	issues
+}
@@ -0,0 +1,393 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::mod_file::Localization;
+use crate::{done, info, warn, NiceUnwrap};
+
+/// Translated strings of a language by key
+pub type Strings = BTreeMap<String, String>;
+
+fn flatten_json(prefix: &str, value: &serde_json::Value, out: &mut Strings) -> Result<(), String> {
+	match value {
+		serde_json::Value::String(text) => {
+			out.insert(prefix.to_string(), text.clone());
+		}
+		serde_json::Value::Object(map) => {
+			for (key, value) in map {
+				let key = if prefix.is_empty() {
+					key.clone()
+				} else {
+					format!("{prefix}.{key}")
+				};
+				flatten_json(&key, value, out)?;
+			}
+		}
+		_ => return Err(format!("Expected '{prefix}' to be a string or an object")),
+	}
+	Ok(())
+}
+
+/// JSON files are objects of strings, where nested objects become dotted keys
+fn parse_json(data: &str) -> Result<Strings, String> {
+	let value: serde_json::Value = serde_json::from_str(data).map_err(|e| e.to_string())?;
+	let mut strings = Strings::new();
+	flatten_json("", &value, &mut strings)?;
+	Ok(strings)
+}
+
+fn unquote_po(line: &str, line_num: usize) -> Result<String, String> {
+	let inner = line
+		.strip_prefix('"')
+		.and_then(|x| x.strip_suffix('"'))
+		.ok_or_else(|| format!("Line {line_num}: expected a quoted string"))?;
+
+	let mut out = String::new();
+	let mut chars = inner.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			out.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n') => out.push('\n'),
+			Some('t') => out.push('\t'),
+			Some('"') => out.push('"'),
+			Some('\\') => out.push('\\'),
+			other => return Err(format!("Line {line_num}: invalid escape '\\{}'", other.unwrap_or(' '))),
+		}
+	}
+	Ok(out)
+}
+
+/// PO files use the msgid as the key. Untranslated and fuzzy entries are
+/// treated as missing, and plural entries use their first form
+fn parse_po(data: &str) -> Result<Strings, String> {
+	#[derive(PartialEq)]
+	enum Field {
+		None,
+		Context,
+		Id,
+		Str,
+		Other,
+	}
+
+	let mut strings = Strings::new();
+
+	let (mut context, mut id, mut text) = (String::new(), String::new(), String::new());
+	let mut fuzzy = false;
+	let mut field = Field::None;
+
+	let mut finish = |context: &mut String, id: &mut String, text: &mut String, fuzzy: &mut bool| {
+		// The entry with an empty msgid is the header
+		if !id.is_empty() && !text.is_empty() && !*fuzzy {
+			let key = if context.is_empty() {
+				id.clone()
+			} else {
+				format!("{context}.{id}")
+			};
+			strings.insert(key, std::mem::take(text));
+		}
+		context.clear();
+		id.clear();
+		text.clear();
+		*fuzzy = false;
+	};
+
+	for (i, line) in data.lines().enumerate() {
+		let line = line.trim();
+		let line_num = i + 1;
+
+		if line.is_empty() {
+			continue;
+		}
+
+		// Comments and keys after a msgstr start the next entry
+		let starts_entry = line.starts_with('#') || line.starts_with("msgctxt") || line.starts_with("msgid ");
+		if starts_entry && (field == Field::Str || field == Field::Other) {
+			finish(&mut context, &mut id, &mut text, &mut fuzzy);
+			field = Field::None;
+		}
+
+		if let Some(comment) = line.strip_prefix('#') {
+			if comment.starts_with(',') && comment.contains("fuzzy") {
+				fuzzy = true;
+			}
+			continue;
+		}
+
+		let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+		let rest = rest.trim();
+		match keyword {
+			"msgctxt" => {
+				field = Field::Context;
+				context = unquote_po(rest, line_num)?;
+			}
+			"msgid" => {
+				field = Field::Id;
+				id = unquote_po(rest, line_num)?;
+			}
+			"msgstr" | "msgstr[0]" => {
+				field = Field::Str;
+				text = unquote_po(rest, line_num)?;
+			}
+			"msgid_plural" => field = Field::Other,
+			_ if keyword.starts_with("msgstr[") => field = Field::Other,
+			_ if line.starts_with('"') => {
+				let continued = unquote_po(line, line_num)?;
+				match field {
+					Field::Context => context += &continued,
+					Field::Id => id += &continued,
+					Field::Str => text += &continued,
+					Field::Other => {}
+					Field::None => return Err(format!("Line {line_num}: unexpected string")),
+				}
+			}
+			_ => return Err(format!("Line {line_num}: unknown keyword '{keyword}'")),
+		}
+	}
+	finish(&mut context, &mut id, &mut text, &mut fuzzy);
+
+	Ok(strings)
+}
+
+/// Fluent files are read as simple messages. Attributes become dotted keys,
+/// and terms (`-term`) are only used by other messages so they're skipped
+fn parse_fluent(data: &str) -> Result<Strings, String> {
+	let mut strings = Strings::new();
+	let mut current: Option<String> = None;
+
+	for (i, line) in data.lines().enumerate() {
+		let line_num = i + 1;
+
+		if line.trim().is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let indented = line.starts_with(' ') || line.starts_with('\t');
+		let trimmed = line.trim();
+
+		// Attributes of the message above
+		if indented && trimmed.starts_with('.') {
+			let (attr, value) = trimmed[1..]
+				.split_once('=')
+				.ok_or_else(|| format!("Line {line_num}: expected '=' after attribute"))?;
+			let Some(message) = current.as_ref().and_then(|x| x.split('.').next().map(str::to_string)) else {
+				return Err(format!("Line {line_num}: attribute without a message"));
+			};
+			let key = format!("{message}.{}", attr.trim());
+			strings.insert(key.clone(), value.trim().to_string());
+			current = Some(key);
+		}
+		// Continuation of a multiline value
+		else if indented {
+			if let Some(key) = &current {
+				let value = strings.get_mut(key).unwrap();
+				if !value.is_empty() {
+					value.push('\n');
+				}
+				value.push_str(trimmed);
+			}
+		}
+		else {
+			let (key, value) = trimmed
+				.split_once('=')
+				.ok_or_else(|| format!("Line {line_num}: expected 'key = value'"))?;
+			let key = key.trim();
+			if key.starts_with('-') {
+				current = None;
+				continue;
+			}
+			strings.insert(key.to_string(), value.trim().to_string());
+			current = Some(key.to_string());
+		}
+	}
+
+	// Messages may only have attributes
+	strings.retain(|_, value| !value.is_empty());
+
+	Ok(strings)
+}
+
+/// Read a language file based on its extension
+pub fn read_language_file(path: &Path) -> Result<Strings, String> {
+	let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+	match path.extension().and_then(|x| x.to_str()) {
+		Some("json") => parse_json(&data),
+		Some("po") => parse_po(&data),
+		Some("ftl") => parse_fluent(&data),
+		_ => Err("Unsupported language file, expected .json, .po or .ftl".into()),
+	}
+}
+
+/// Placeholders in a string, normalized so they can be compared across
+/// formats. Supports `{name}`, Fluent's `{ $name }` and printf's `%s` / `%1$d`
+fn placeholders(text: &str) -> Vec<String> {
+	let mut found = Vec::new();
+	let mut chars = text.char_indices();
+	while let Some((i, c)) = chars.next() {
+		match c {
+			'{' => {
+				if let Some(end) = text[i..].find('}') {
+					let inner = text[i + 1..i + end].trim().trim_start_matches('$');
+					if !inner.is_empty() {
+						found.push(format!("{{{inner}}}"));
+					}
+				}
+			}
+			'%' => {
+				// Flags, width, precision and positions, then the conversion
+				let spec: String = text[i + 1..]
+					.chars()
+					.take_while(|c| c.is_ascii_digit() || matches!(c, '$' | '.' | '-' | 'l'))
+					.collect();
+				let Some(conversion) = text[i + 1 + spec.len()..].chars().next() else {
+					continue;
+				};
+				if "sdifuxXeEgGcp@".contains(conversion) {
+					found.push(format!("%{spec}{conversion}"));
+					chars.nth(spec.len());
+				}
+				// `%%` is an escaped percent sign
+				else if conversion == '%' && spec.is_empty() {
+					chars.next();
+				}
+			}
+			_ => {}
+		}
+	}
+	found.sort();
+	found
+}
+
+/// Fluent placeholders are written as `{name}` like in the other formats
+fn normalize(text: &str) -> String {
+	let mut out = String::new();
+	let mut rest = text;
+	while let Some(start) = rest.find('{') {
+		out += &rest[..start];
+		match rest[start..].find('}') {
+			Some(end) => {
+				let inner = rest[start + 1..start + end].trim();
+				match inner.strip_prefix('$') {
+					Some(name) => out += &format!("{{{name}}}"),
+					None => out += &rest[start..start + end + 1],
+				}
+				rest = &rest[start + end + 1..];
+			}
+			None => {
+				out += &rest[start..];
+				rest = "";
+			}
+		}
+	}
+	out + rest
+}
+
+/// Read all languages and check them against the default language
+pub fn read_languages(localization: &Localization) -> BTreeMap<String, Strings> {
+	let languages: BTreeMap<String, Strings> = localization
+		.languages
+		.iter()
+		.map(|(lang, path)| {
+			let strings = read_language_file(path)
+				.nice_unwrap(format!("Unable to read language file '{}'", path.display()));
+			(lang.clone(), strings)
+		})
+		.collect();
+
+	let Some(default) = languages.get(&localization.default_language) else {
+		warn!(
+			"Default language '{}' has no language file, skipping validation",
+			localization.default_language
+		);
+		return languages;
+	};
+
+	for (lang, strings) in &languages {
+		if *lang == localization.default_language {
+			continue;
+		}
+
+		let unknown = strings.keys().filter(|x| !default.contains_key(*x)).collect::<Vec<_>>();
+		if !unknown.is_empty() {
+			warn!(
+				"Language '{}' has keys missing from '{}': {}",
+				lang,
+				localization.default_language,
+				unknown.iter().map(|x| x.as_str()).collect::<Vec<_>>().join(", ")
+			);
+		}
+
+		let missing = default.keys().filter(|x| !strings.contains_key(*x)).collect::<Vec<_>>();
+		if !missing.is_empty() {
+			warn!(
+				"Language '{}' is missing {} translation(s), which fall back to '{}': {}",
+				lang,
+				missing.len(),
+				localization.default_language,
+				missing.iter().map(|x| x.as_str()).collect::<Vec<_>>().join(", ")
+			);
+		}
+
+		for (key, text) in strings {
+			let Some(default_text) = default.get(key) else { continue };
+			if placeholders(text) != placeholders(default_text) {
+				warn!(
+					"Placeholders of '{}' in language '{}' don't match '{}': {:?} vs {:?}",
+					key,
+					lang,
+					localization.default_language,
+					placeholders(text),
+					placeholders(default_text)
+				);
+			}
+		}
+	}
+
+	languages
+}
+
+/// Every character used by any language, for adding to font charsets
+pub fn used_chars(languages: &BTreeMap<String, Strings>) -> BTreeSet<char> {
+	languages
+		.values()
+		.flat_map(|x| x.values())
+		.flat_map(|x| x.chars())
+		.filter(|x| !x.is_control())
+		.collect()
+}
+
+/// Write each language as a flat JSON object into `lang/<language>.json`,
+/// with missing translations filled in from the default language
+pub fn write_languages(
+	localization: &Localization,
+	languages: &BTreeMap<String, Strings>,
+	output_dir: &Path,
+	shut_up: bool,
+) {
+	if !shut_up {
+		info!("Compiling localization");
+	}
+
+	let lang_dir = output_dir.join("lang");
+	fs::create_dir_all(&lang_dir).nice_unwrap("Unable to create localization directory");
+
+	let default = languages.get(&localization.default_language);
+	for (lang, strings) in languages {
+		let mut compiled = default.cloned().unwrap_or_default();
+		compiled.extend(strings.iter().map(|(k, v)| (k.clone(), v.clone())));
+		let compiled = compiled
+			.into_iter()
+			.map(|(k, v)| (k, normalize(&v)))
+			.collect::<BTreeMap<_, _>>();
+
+		fs::write(
+			lang_dir.join(format!("{lang}.json")),
+			serde_json::to_string_pretty(&compiled).unwrap(),
+		)
+		.nice_unwrap(format!("Unable to write language '{lang}'"));
+	}
+
+	done!("Compiled {} language(s)", languages.len());
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Name of the lockfile, which is kept next to mod.json
+pub const LOCKFILE_NAME: &str = "mod.lock.json";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum LockSource {
+	Index,
+	Installed,
+	External,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockedDependency {
+	/// Exact version, like `v1.2.0`. Missing for externals given without one
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub version: Option<String>,
+	pub source: LockSource,
+	/// SHA3-256 of the package. Missing for externals
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub hash: Option<String>,
+}
+
+impl LockedDependency {
+	/// Whether both lock the same package; where it came from doesn't matter
+	fn same_package(&self, other: &LockedDependency) -> bool {
+		self.version == other.version && self.hash == other.hash
+	}
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Lockfile {
+	#[serde(default)]
+	pub dependencies: BTreeMap<String, LockedDependency>,
+}
+
+impl Lockfile {
+	/// Read the lockfile of the project in `dir`, if it has one
+	pub fn read(dir: &Path) -> Result<Option<Lockfile>, String> {
+		let path = dir.join(LOCKFILE_NAME);
+		if !path.exists() {
+			return Ok(None);
+		}
+		let data = fs::read_to_string(&path).map_err(|e| format!("Unable to read {LOCKFILE_NAME}: {e}"))?;
+		serde_json::from_str(&data)
+			.map(Some)
+			.map_err(|e| format!("Unable to parse {LOCKFILE_NAME}: {e}"))
+	}
+
+	pub fn write(&self, dir: &Path) -> Result<(), String> {
+		fs::write(dir.join(LOCKFILE_NAME), serde_json::to_string_pretty(self).unwrap() + "\n")
+			.map_err(|e| format!("Unable to write {LOCKFILE_NAME}: {e}"))
+	}
+
+	/// Keep the recorded source of packages that didn't change, so that the
+	/// lockfile doesn't change just because someone has a mod installed
+	pub fn keep_sources(&mut self, old: &Lockfile) {
+		for (id, dep) in &mut self.dependencies {
+			if let Some(old_dep) = old.dependencies.get(id).filter(|x| x.same_package(dep)) {
+				dep.source = old_dep.source;
+			}
+		}
+	}
+
+	/// Dependencies locked at the same version in an older lockfile, but with
+	/// a package that has a different hash
+	pub fn hash_mismatches(&self, old: &Lockfile) -> Vec<String> {
+		self.dependencies
+			.iter()
+			.filter(|(id, dep)| {
+				old.dependencies
+					.get(*id)
+					.is_some_and(|x| x.version == dep.version && x.hash != dep.hash)
+			})
+			.map(|(id, _)| id.clone())
+			.collect()
+	}
+
+	/// Descriptions of how this lockfile differs from an older one
+	pub fn changes_from(&self, old: &Lockfile) -> Vec<String> {
+		let describe = |dep: &LockedDependency| dep.version.clone().unwrap_or("external".into());
+
+		let mut changes = Vec::new();
+		for (id, dep) in &self.dependencies {
+			match old.dependencies.get(id) {
+				None => changes.push(format!("Added '{}' {}", id, describe(dep))),
+				Some(old_dep) if old_dep.version != dep.version => changes.push(format!(
+					"Changed '{}' {} => {}",
+					id, describe(old_dep), describe(dep)
+				)),
+				Some(old_dep) if old_dep.hash != dep.hash => changes.push(format!(
+					"Package of '{}' {} has a different hash than when it was locked",
+					id, describe(dep)
+				)),
+				Some(_) => {}
+			}
+		}
+		for id in old.dependencies.keys() {
+			if !self.dependencies.contains_key(id) {
+				changes.push(format!("Removed '{}'", id));
+			}
+		}
+		changes
+	}
+}
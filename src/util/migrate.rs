@@ -0,0 +1,108 @@
+use semver::Version;
+use serde_json::{json, Value};
+
+use crate::jsonc::JsonEditor;
+use crate::version;
+
+struct Migration {
+	/// Loader version that expects the change
+	version: &'static str,
+	description: &'static str,
+	apply: fn(&mut JsonEditor) -> Result<(), String>,
+}
+
+/// Changes to mod.json by loader version, oldest first. Dependency importance
+/// in 1.0.0 is the only change to the mod.json format so far; later versions
+/// read the same keys, so migrating to them only updates the target version.
+/// Add an entry here whenever a loader version renames or reshapes a key
+const MIGRATIONS: &[Migration] = &[Migration {
+	version: "1.0.0",
+	description: "Replace 'required' in dependencies with 'importance'",
+	apply: dependency_importance,
+}];
+
+fn dependency_importance(editor: &mut JsonEditor) -> Result<(), String> {
+	let count = editor
+		.get(&["dependencies"])
+		.and_then(|x| x.as_array().map(|x| x.len()))
+		.unwrap_or(0);
+
+	for i in 0..count {
+		let i = i.to_string();
+		let Some(Value::Bool(required)) = editor.get(&["dependencies", &i, "required"]) else {
+			continue;
+		};
+
+		// An explicit importance already wins over 'required'
+		if editor.get(&["dependencies", &i, "importance"]).is_some() {
+			editor.remove(&["dependencies", &i, "required"]);
+			continue;
+		}
+
+		let importance = if required { "required" } else { "recommended" };
+		editor.set(&["dependencies", &i, "required"], &json!(importance))?;
+		editor.rename(&["dependencies", &i, "required"], "importance");
+	}
+	Ok(())
+}
+
+/// Apply every migration for loader versions after `from` up to and
+/// including `to`, and retarget the mod to `to`. Returns the new text along
+/// with descriptions of the migrations applied
+pub fn migrate(text: &str, from: &Version, to: &Version) -> Result<(String, Vec<&'static str>), String> {
+	let mut editor = JsonEditor::new(text.to_string()).map_err(|e| format!("Unable to parse mod.json: {e}"))?;
+
+	let mut applied = Vec::new();
+	for migration in MIGRATIONS {
+		let version = version::parse_version(migration.version).unwrap();
+		if *from < version && version <= *to {
+			(migration.apply)(&mut editor)?;
+			applied.push(migration.description);
+		}
+	}
+
+	editor.set(&[""], &json!(format!("v{to}")))?;
+
+	Ok((editor.text().to_string(), applied))
+}
+
+pub enum DiffLine<'a> {
+	Same(&'a str),
+	Removed(&'a str),
+	Added(&'a str),
+}
+
+/// Line by line diff of two texts, through their longest common subsequence
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+	let old = old.lines().collect::<Vec<_>>();
+	let new = new.lines().collect::<Vec<_>>();
+
+	// lengths[i][j] is the length of the LCS of old[i..] and new[j..]
+	let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+	for i in (0..old.len()).rev() {
+		for j in (0..new.len()).rev() {
+			lengths[i][j] = if old[i] == new[j] {
+				lengths[i + 1][j + 1] + 1
+			} else {
+				lengths[i + 1][j].max(lengths[i][j + 1])
+			};
+		}
+	}
+
+	let mut out = Vec::new();
+	let (mut i, mut j) = (0, 0);
+	while i < old.len() || j < new.len() {
+		if i < old.len() && j < new.len() && old[i] == new[j] {
+			out.push(DiffLine::Same(old[i]));
+			i += 1;
+			j += 1;
+		} else if i < old.len() && (j == new.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+			out.push(DiffLine::Removed(old[i]));
+			i += 1;
+		} else {
+			out.push(DiffLine::Added(new[j]));
+			j += 1;
+		}
+	}
+	out
+}
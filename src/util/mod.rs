@@ -1,10 +1,25 @@
+pub mod audio;
 pub mod bmfont;
 pub mod cache;
 pub mod config;
+pub mod depfile;
+pub mod glsl;
+pub mod jsonc;
+pub mod lint;
+pub mod migrate;
+pub mod localization;
+pub mod lockfile;
 pub mod logging;
 pub mod mod_file;
+pub mod optimize;
+pub mod pvr;
+pub mod report;
+pub mod resolver;
+pub mod schema;
+pub mod shader;
 pub mod rgba4444;
 pub mod spritesheet;
+pub mod version;
 
 pub use logging::NiceUnwrap;
 
@@ -1,23 +1,72 @@
+use path_absolutize::Absolutize;
 use semver::{VersionReq, Version};
-use serde::{Deserialize, Deserializer};
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
 use std::fs;
 use std::io::Read;
 use std::path::{PathBuf, Path};
-use crate::spritesheet::SpriteSheet;
+use crate::jsonc;
+use crate::spritesheet::{SpriteFile, SpriteSheet};
+use crate::version;
 use crate::NiceUnwrap;
 
+thread_local! {
+	/// Directory that globs and relative paths of the file currently being
+	/// parsed are resolved against. None when the file was read from a
+	/// package, in which case paths are left as written
+	static PARSE_ROOT: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Sets the parse root while alive and restores the previous one when
+/// dropped, including when parsing fails
+struct ParseRootGuard {
+	previous: Option<PathBuf>,
+}
+
+impl ParseRootGuard {
+	fn new(root: Option<PathBuf>) -> Self {
+		ParseRootGuard {
+			previous: PARSE_ROOT.with(|x| x.replace(root)),
+		}
+	}
+}
+
+impl Drop for ParseRootGuard {
+	fn drop(&mut self) {
+		PARSE_ROOT.with(|x| *x.borrow_mut() = self.previous.take());
+	}
+}
+
+fn parse_root() -> Option<PathBuf> {
+	PARSE_ROOT.with(|x| x.borrow().clone())
+}
+
+/// Resolve a path relative to the parse root
+fn resolve_path(path: PathBuf) -> PathBuf {
+	match parse_root() {
+		Some(root) => root.join(path),
+		None => path,
+	}
+}
+
 trait Glob {
 	fn glob(self) -> Self;
 }
 
 impl Glob for Vec<PathBuf> {
 	fn glob(self) -> Self {
+		let Some(root) = parse_root() else {
+			return self;
+		};
+		// Only the paths from mod.json are patterns, not the folder it's in
+		let root = PathBuf::from(glob::Pattern::escape(root.to_str().unwrap()));
 		self
 			.into_iter()
 			.flat_map(|src|
 				glob::glob(
-					std::env::current_dir().unwrap()
+					root
 						.join(&src)
 						.to_str()
 						.unwrap()
@@ -42,21 +91,54 @@ where
     Ok(Vec::<PathBuf>::deserialize(deserializer)?
 		.glob()
 		.into_iter()
-		.map(|p| p.strip_prefix(std::env::current_dir().unwrap()).unwrap_or(&p).to_path_buf())
+		.map(|p| match parse_root() {
+			Some(root) => p.strip_prefix(root).unwrap_or(&p).to_path_buf(),
+			None => p,
+		})
 		.collect()
 	)
 }
 
+/// A sprite glob, optionally with the UHD size to rasterize SVGs at
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SpriteSource {
+	Path(PathBuf),
+	Sized {
+		path: PathBuf,
+		size: (u32, u32),
+	},
+}
+
+fn sprite_files(srcs: Vec<SpriteSource>) -> Vec<SpriteFile> {
+	srcs.into_iter()
+		.flat_map(|src| {
+			let (path, size) = match src {
+				SpriteSource::Path(path) => (path, None),
+				SpriteSource::Sized { path, size } => (path, Some(size)),
+			};
+			vec![path].glob().into_iter().map(move |path| SpriteFile { path, size })
+		})
+		.collect()
+}
+
+fn parse_sprites<'de, D>(deserializer: D) -> Result<Vec<SpriteFile>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+	Ok(sprite_files(Vec::<SpriteSource>::deserialize(deserializer)?))
+}
+
 fn parse_spritesheets<'de, D>(deserializer: D) -> Result<HashMap<String, SpriteSheet>, D::Error>
 where
     D: Deserializer<'de>,
 {
-	Ok(HashMap::<String, Vec<PathBuf>>::deserialize(deserializer)?
+	Ok(HashMap::<String, Vec<SpriteSource>>::deserialize(deserializer)?
 		.into_iter()
         .map(|(name, srcs)| {
 			(name.clone(), SpriteSheet {
 				name,
-				files: srcs.glob()
+				files: sprite_files(srcs)
 			})
         })
 		.collect()
@@ -67,22 +149,14 @@ fn parse_version<'de, D>(deserializer: D) -> Result<Version, D::Error>
 where
     D: Deserializer<'de>,
 {
-	// semver doesn't accept "v" prefixes and the string will be validated at 
-	// runtime by Sapphire anyway so let's just crudely remove all 'v's for now
-	Ok(Version::parse(&<String>::deserialize(deserializer)?.replace("v", ""))
-		.map_err(serde::de::Error::custom)?
-	)
+	version::parse_version(&<String>::deserialize(deserializer)?).map_err(serde::de::Error::custom)
 }
 
 fn parse_comparable_version<'de, D>(deserializer: D) -> Result<VersionReq, D::Error>
 where
     D: Deserializer<'de>,
 {
-	// semver doesn't accept "v" prefixes and the string will be validated at 
-	// runtime by Sapphire anyway so let's just crudely remove all 'v's for now
-	Ok(VersionReq::parse(&<String>::deserialize(deserializer)?.replace("v", ""))
-		.map_err(serde::de::Error::custom)?
-	)
+	version::parse_version_req(&<String>::deserialize(deserializer)?).map_err(serde::de::Error::custom)
 }
 
 fn parse_fonts<'de, D>(deserializer: D) -> Result<HashMap<String, BitmapFont>, D::Error>
@@ -93,7 +167,7 @@ where
 		.into_iter()
 		.map(|(name, mut font)| {
 			font.name = name.clone();
-			font.path = std::env::current_dir().unwrap().join(font.path);
+			font.path = resolve_path(font.path);
 			(name, font)
 		})
 		.collect()
@@ -158,7 +232,7 @@ impl Color {
     }
 }
 
-#[derive(Deserialize, PartialEq)]
+#[derive(Deserialize, PartialEq, Clone)]
 pub struct BitmapFont {
 	#[serde(skip)]
 	pub name: String,
@@ -169,6 +243,64 @@ pub struct BitmapFont {
 	pub outline: u32,
 	#[serde(default = "Color::white", deserialize_with = "parse_color")]
 	pub color: Color,
+	#[serde(default, rename = "faceIndex")]
+	pub face_index: u32,
+	pub instance: Option<String>,
+	#[serde(default)]
+	pub variations: BTreeMap<String, f32>,
+}
+
+#[derive(Default, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum TextureFormat {
+	#[default]
+	#[serde(rename = "png")]
+	Png,
+	#[serde(rename = "pvr.ccz")]
+	PvrCcz,
+	/// Write both, with the plist or font pointing to the .pvr.ccz
+	#[serde(rename = "both")]
+	Both,
+}
+
+#[derive(Default, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PixelFormat {
+	#[default]
+	Rgba8888,
+	Rgba4444,
+}
+
+/// How hard to try making generated PNGs smaller. Optimization is always
+/// lossless
+#[derive(Default, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PngOptimization {
+	#[default]
+	None,
+	Fast,
+	Max,
+}
+
+/// Output format of generated textures
+#[derive(Default, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextureOutput {
+	#[serde(default)]
+	pub format: TextureFormat,
+	#[serde(default)]
+	pub pixel_format: PixelFormat,
+	#[serde(default)]
+	pub png_optimization: PngOptimization,
+}
+
+impl TextureOutput {
+	pub fn png(&self) -> bool {
+		self.format != TextureFormat::PvrCcz
+	}
+
+	pub fn pvr(&self) -> bool {
+		self.format != TextureFormat::Png
+	}
 }
 
 #[derive(Default, Deserialize, PartialEq)]
@@ -182,14 +314,109 @@ pub struct ModResources {
 	#[serde(deserialize_with = "parse_spritesheets", default = "HashMap::new")]
 	pub spritesheets: HashMap<String, SpriteSheet>,
 
-	#[serde(deserialize_with = "parse_glob", default = "Vec::new")]
-	pub sprites: Vec<PathBuf>,
+	#[serde(deserialize_with = "parse_sprites", default = "Vec::new")]
+	pub sprites: Vec<SpriteFile>,
 
 	#[serde(deserialize_with = "parse_fonts", default = "HashMap::new")]
 	pub fonts: HashMap<String, BitmapFont>,
+
+	#[serde(default, rename = "textureFormat")]
+	pub texture_format: TextureOutput,
+
+	/// Write a descriptor of the animations in each spritesheet
+	#[serde(default, rename = "animationDescriptors")]
+	pub animation_descriptors: bool,
+
+	#[serde(default)]
+	pub localization: Localization,
+
+	/// GLSL ES vertex (.vsh) and fragment (.fsh) shaders, parsed and
+	/// validated when building
+	#[serde(deserialize_with = "parse_glob", default = "Vec::new")]
+	pub shaders: Vec<PathBuf>,
+
+	#[serde(default, rename = "minifyShaders")]
+	pub minify_shaders: bool,
+
+	#[serde(default)]
+	pub sounds: SoundResources,
 }
 
-#[derive(Default, Deserialize, PartialEq)]
+fn default_sound_quality() -> f32 {
+	0.4
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct SoundResources {
+	/// .wav, .ogg or .mp3 files
+	#[serde(deserialize_with = "parse_glob", default = "Vec::new")]
+	pub files: Vec<PathBuf>,
+
+	/// Transcode WAVs and OGGs to OGG Vorbis, named `<name>.ogg`
+	#[serde(default)]
+	pub transcode: bool,
+
+	/// Vorbis quality from -0.2 to 1.0
+	#[serde(default = "default_sound_quality")]
+	pub quality: f32,
+
+	/// Peak level in dBFS to normalize transcoded sounds to
+	pub normalize: Option<f32>,
+}
+
+impl Default for SoundResources {
+	fn default() -> Self {
+		SoundResources {
+			files: Vec::new(),
+			transcode: false,
+			quality: default_sound_quality(),
+			normalize: None,
+		}
+	}
+}
+
+fn default_language() -> String {
+	"en".into()
+}
+
+fn parse_language_files<'de, D>(deserializer: D) -> Result<BTreeMap<String, PathBuf>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+	Ok(<BTreeMap<String, PathBuf>>::deserialize(deserializer)?
+		.into_iter()
+		.map(|(lang, path)| (lang, resolve_path(path)))
+		.collect()
+	)
+}
+
+#[derive(Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Localization {
+	/// Language the others are checked against and fall back to
+	#[serde(default = "default_language")]
+	pub default_language: String,
+
+	/// Language files (.json, .po or .ftl) by language code
+	#[serde(deserialize_with = "parse_language_files", default)]
+	pub languages: BTreeMap<String, PathBuf>,
+
+	/// Add every character used in translations to the charsets of all fonts
+	#[serde(default)]
+	pub font_charsets: bool,
+}
+
+impl Default for Localization {
+	fn default() -> Self {
+		Localization {
+			default_language: default_language(),
+			languages: BTreeMap::new(),
+			font_charsets: false,
+		}
+	}
+}
+
+#[derive(Default, Deserialize, PartialEq, Clone, Copy, Debug, clap::ValueEnum)]
 #[serde(rename_all = "camelCase")]
 pub enum DependencyImportance {
 	#[default]
@@ -197,6 +424,16 @@ pub enum DependencyImportance {
 	Recommended,
 	Suggested,
 }
+
+impl std::fmt::Display for DependencyImportance {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			DependencyImportance::Required => "required",
+			DependencyImportance::Recommended => "recommended",
+			DependencyImportance::Suggested => "suggested",
+		})
+	}
+}
 #[derive(Default, Deserialize, PartialEq)]
 pub struct Dependency {
 	pub id: String,
@@ -207,12 +444,174 @@ pub struct Dependency {
 	pub required: Option<bool>,
 }
 
+impl Dependency {
+	/// Whether the mod can't be built or loaded without this dependency
+	pub fn is_required(&self) -> bool {
+		self.importance == DependencyImportance::Required || self.required == Some(true)
+	}
+}
+
 #[derive(Default, Deserialize, PartialEq)]
 pub struct ModApi {
 	#[serde(deserialize_with = "parse_glob_rel")]
 	pub include: Vec<PathBuf>,
 }
 
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+#[serde(from = "String")]
+pub enum SettingType {
+	Bool,
+	Int,
+	Float,
+	String,
+	Color,
+	/// Color with alpha
+	Rgba,
+	File,
+	/// Implemented by the mod itself, so only the mod knows what's valid
+	Custom,
+	/// Any type this version doesn't know about. Mods using one still parse,
+	/// so they show up in the index and among installed mods
+	Other(String),
+}
+
+impl From<String> for SettingType {
+	fn from(value: String) -> Self {
+		match value.as_str() {
+			"bool" => SettingType::Bool,
+			"int" => SettingType::Int,
+			"float" => SettingType::Float,
+			"string" => SettingType::String,
+			"color" => SettingType::Color,
+			"rgba" => SettingType::Rgba,
+			"file" => SettingType::File,
+			"custom" => SettingType::Custom,
+			_ => SettingType::Other(value),
+		}
+	}
+}
+
+impl std::fmt::Display for SettingType {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			SettingType::Bool => "bool",
+			SettingType::Int => "int",
+			SettingType::Float => "float",
+			SettingType::String => "string",
+			SettingType::Color => "color",
+			SettingType::Rgba => "rgba",
+			SettingType::File => "file",
+			SettingType::Custom => "custom",
+			SettingType::Other(name) => name,
+		})
+	}
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Setting {
+	#[serde(rename = "type")]
+	pub kind: SettingType,
+	pub name: Option<String>,
+	pub description: Option<String>,
+	pub default: Option<serde_json::Value>,
+	/// Bounds of int and float settings
+	pub min: Option<f64>,
+	pub max: Option<f64>,
+	/// Allowed values of string settings
+	#[serde(rename = "one-of")]
+	pub one_of: Option<Vec<String>>,
+}
+
+impl Setting {
+	/// Problems the loader would reject the setting for
+	pub fn validate(&self) -> Vec<String> {
+		if let SettingType::Other(name) = &self.kind {
+			return vec![format!("Unknown setting type '{name}'")];
+		}
+
+		let mut issues = Vec::new();
+
+		let numeric = matches!(self.kind, SettingType::Int | SettingType::Float);
+		if !numeric && (self.min.is_some() || self.max.is_some()) {
+			issues.push(format!("'min' and 'max' don't apply to {} settings", self.kind));
+		}
+		if self.one_of.is_some() && self.kind != SettingType::String {
+			issues.push(format!("'one-of' doesn't apply to {} settings", self.kind));
+		}
+		if let (Some(min), Some(max)) = (self.min, self.max) {
+			if min > max {
+				issues.push(format!("'min' ({min}) is larger than 'max' ({max})"));
+			}
+		}
+		if self.one_of.as_ref().is_some_and(|x| x.is_empty()) {
+			issues.push("'one-of' has no options".into());
+		}
+
+		let Some(default) = &self.default else {
+			if self.kind != SettingType::Custom {
+				issues.push("Missing 'default'".into());
+			}
+			return issues;
+		};
+
+		match (&self.kind, default) {
+			(SettingType::Bool, serde_json::Value::Bool(_)) => {}
+			(SettingType::Int | SettingType::Float, serde_json::Value::Number(number)) => {
+				if self.kind == SettingType::Int && !number.is_i64() {
+					issues.push(format!("Default value {number} is not an integer"));
+				}
+				let value = number.as_f64().unwrap_or_default();
+				if self.min.is_some_and(|min| value < min) || self.max.is_some_and(|max| value > max) {
+					issues.push(format!(
+						"Default value {number} is outside of the range {} to {}",
+						self.min.map(|x| x.to_string()).unwrap_or("-inf".into()),
+						self.max.map(|x| x.to_string()).unwrap_or("inf".into()),
+					));
+				}
+			}
+			(SettingType::String, serde_json::Value::String(value)) => {
+				if let Some(options) = &self.one_of {
+					if !options.contains(value) {
+						issues.push(format!(
+							"Default value '{value}' is not one of {}",
+							options.iter().map(|x| format!("'{x}'")).collect::<Vec<_>>().join(", ")
+						));
+					}
+				}
+			}
+			(SettingType::Color, serde_json::Value::String(value)) => {
+				if let Err(e) = Color::parse_hex(value) {
+					issues.push(format!("Default value '{value}' is not a color: {e}"));
+				}
+			}
+			// Can be written as a hex string or as components
+			(SettingType::Rgba, _) => {}
+			(SettingType::File, serde_json::Value::String(_)) => {}
+			(SettingType::Custom | SettingType::Other(_), _) => {}
+			(kind, default) => {
+				issues.push(format!("Default value {default} doesn't match the type {kind}"));
+			}
+		}
+
+		issues
+	}
+}
+
+/// Problems with the settings of a mod, as `(setting ID, problem)`
+pub fn validate_settings(settings: &BTreeMap<String, Setting>) -> Vec<(String, String)> {
+	let mut issues = Vec::new();
+	for (id, setting) in settings {
+		if id.is_empty() || !id.chars().all(|c| matches!(c, 'a'..='z' | '0'..='9' | '-' | '_')) {
+			issues.push((
+				id.clone(),
+				"Setting IDs may only contain lowercase letters, digits, '-' and '_'".into(),
+			));
+		}
+		issues.extend(setting.validate().into_iter().map(|x| (id.clone(), x)));
+	}
+	issues
+}
+
 #[derive(Deserialize, PartialEq)]
 pub struct ModFileInfo {
 	#[serde(deserialize_with = "parse_version")]
@@ -228,9 +627,33 @@ pub struct ModFileInfo {
 	#[serde(default)]
 	pub dependencies: Vec<Dependency>,
 	pub api: Option<ModApi>,
+	#[serde(default)]
+	pub settings: BTreeMap<String, Setting>,
+}
+
+/// Error from reading or parsing a mod.json or pack.json
+pub struct ParseError {
+	pub message: String,
+	/// Line and column of the error, if it's in the JSON itself
+	pub position: Option<(usize, usize)>,
 }
 
-pub fn try_parse_mod_info(root_path: &Path) -> Result<ModFileInfo, String> {
+impl Display for ParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.position {
+			Some((line, column)) => write!(f, "{} at line {} column {}", self.message, line, column),
+			None => write!(f, "{}", self.message),
+		}
+	}
+}
+
+impl From<String> for ParseError {
+	fn from(message: String) -> Self {
+		ParseError { message, position: None }
+	}
+}
+
+pub fn try_parse_mod_info(root_path: &Path) -> Result<ModFileInfo, ParseError> {
 	let data = if root_path.is_dir() {
 		std::fs::read_to_string(root_path.join("mod.json"))
 		.map_err(|e| format!("Unable to read mod.json: {e}"))?
@@ -247,26 +670,57 @@ pub fn try_parse_mod_info(root_path: &Path) -> Result<ModFileInfo, String> {
 		out
 	};
 
-	// to make globs work, relink current directory to the one mod.json is in
-	let old = std::env::current_dir().or(Err("Unable to get current directory"))?;
+	// Resources of a package refer to files that aren't on disk anymore, so
+	// don't glob those
+	parse_relative_to(
+		&data,
+		root_path.is_dir().then_some(root_path),
+		"mod.json",
+	)
+}
 
-	std::env::set_current_dir(
-		if root_path.is_dir() {
-			root_path
-		} else {
-			root_path.parent().unwrap()
-		}
-	).or(Err("Unable to relink working directory"))?;
-	
-	let res = serde_json::from_str(&data)
-		.map_err(|e| format!("Could not parse mod.json: {e}"))?;
-	
-	// then link it back to where-ever it was
-	std::env::set_current_dir(old).or(Err("Unable to reset working directory"))?;
+/// Parse a file, resolving its globs and relative paths against `dir`, or
+/// leaving them as written if there is no directory
+fn parse_relative_to<T: DeserializeOwned>(data: &str, dir: Option<&Path>, file_name: &str) -> Result<T, ParseError> {
+	let root = dir
+		.map(|x| x.absolutize().map(|x| x.to_path_buf()))
+		.transpose()
+		.map_err(|e| format!("Unable to resolve directory of {file_name}: {e}"))?;
+	let _guard = ParseRootGuard::new(root);
 
-	Ok(res)
+	jsonc::from_str(data).map_err(|e| {
+		// serde_json only knows the position of errors in the JSON itself
+		let position = (e.line() > 0).then(|| (e.line(), e.column()));
+		let message = e.to_string();
+		let message = match position {
+			Some((line, column)) => message
+				.strip_suffix(&format!(" at line {line} column {column}"))
+				.unwrap_or(&message)
+				.to_string(),
+			None => message,
+		};
+		ParseError { message: format!("Could not parse {file_name}: {message}"), position }
+	})
 }
 
 pub fn parse_mod_info(root_path: &Path) -> ModFileInfo {
 	try_parse_mod_info(root_path).nice_unwrap("Failed to parse mod.json")
 }
+
+#[derive(Deserialize, PartialEq)]
+pub struct TexturePackInfo {
+	pub name: String,
+	#[serde(default)]
+	pub resources: ModResources,
+}
+
+pub fn try_parse_texture_pack_info(root_path: &Path) -> Result<TexturePackInfo, ParseError> {
+	let data = std::fs::read_to_string(root_path.join("pack.json"))
+		.map_err(|e| format!("Unable to read pack.json: {e}"))?;
+
+	parse_relative_to(&data, Some(root_path), "pack.json")
+}
+
+pub fn parse_texture_pack_info(root_path: &Path) -> TexturePackInfo {
+	try_parse_texture_pack_info(root_path).nice_unwrap("Failed to parse pack.json")
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{ImageFormat, RgbaImage};
+use png::{AdaptiveFilterType, BitDepth, ColorType, Compression, FilterType};
+
+use crate::mod_file::PngOptimization;
+
+/// Image data in the smallest color type that represents it losslessly
+struct Reduced {
+	color: ColorType,
+	depth: BitDepth,
+	data: Vec<u8>,
+	palette: Option<Vec<u8>>,
+	trns: Option<Vec<u8>>,
+}
+
+/// Build a palette if the image has at most 256 colors
+fn palette_of(img: &RgbaImage) -> Option<Vec<[u8; 4]>> {
+	let mut colors = HashMap::new();
+	for pixel in img.pixels() {
+		colors.insert(pixel.0, ());
+		if colors.len() > 256 {
+			return None;
+		}
+	}
+
+	// Translucent colors go first so the tRNS chunk can stop at the last one,
+	// and sorting keeps the output identical for the same input
+	let mut palette: Vec<_> = colors.into_keys().collect();
+	palette.sort_by_key(|c| (c[3] == 255, *c));
+	Some(palette)
+}
+
+fn reduce(img: &RgbaImage) -> Reduced {
+	if let Some(palette) = palette_of(img) {
+		let depth = match palette.len() {
+			0..=2 => BitDepth::One,
+			3..=4 => BitDepth::Two,
+			5..=16 => BitDepth::Four,
+			_ => BitDepth::Eight,
+		};
+		let bits = depth as usize;
+		let indices: HashMap<[u8; 4], u8> = palette
+			.iter()
+			.enumerate()
+			.map(|(i, c)| (*c, i as u8))
+			.collect();
+
+		// Pack indices into rows, most significant bits first
+		let row_len = (img.width() as usize * bits).div_ceil(8);
+		let mut data = vec![0u8; row_len * img.height() as usize];
+		for (x, y, pixel) in img.enumerate_pixels() {
+			let bit = x as usize * bits;
+			data[y as usize * row_len + bit / 8] |= indices[&pixel.0] << (8 - bits - bit % 8);
+		}
+
+		let translucent = palette.iter().take_while(|c| c[3] != 255).count();
+		return Reduced {
+			color: ColorType::Indexed,
+			depth,
+			data,
+			palette: Some(palette.iter().flat_map(|c| [c[0], c[1], c[2]]).collect()),
+			trns: (translucent > 0).then(|| palette[..translucent].iter().map(|c| c[3]).collect()),
+		};
+	}
+
+	let opaque = img.pixels().all(|p| p[3] == 255);
+	let gray = img.pixels().all(|p| p[0] == p[1] && p[1] == p[2]);
+
+	let (color, channels): (_, &[usize]) = match (gray, opaque) {
+		(true, true) => (ColorType::Grayscale, &[0]),
+		(true, false) => (ColorType::GrayscaleAlpha, &[0, 3]),
+		(false, true) => (ColorType::Rgb, &[0, 1, 2]),
+		(false, false) => (ColorType::Rgba, &[0, 1, 2, 3]),
+	};
+	Reduced {
+		color,
+		depth: BitDepth::Eight,
+		data: img
+			.pixels()
+			.flat_map(|p| channels.iter().map(|c| p[*c]))
+			.collect(),
+		palette: None,
+		trns: None,
+	}
+}
+
+fn encode(
+	reduced: &Reduced,
+	width: u32,
+	height: u32,
+	compression: Compression,
+	filter: Option<FilterType>,
+) -> std::io::Result<Vec<u8>> {
+	let mut out = Vec::new();
+
+	let mut encoder = png::Encoder::new(&mut out, width, height);
+	encoder.set_color(reduced.color);
+	encoder.set_depth(reduced.depth);
+	if let Some(palette) = &reduced.palette {
+		encoder.set_palette(palette.as_slice());
+	}
+	if let Some(trns) = &reduced.trns {
+		encoder.set_trns(trns.as_slice());
+	}
+	encoder.set_compression(compression);
+
+	// No filter means picking one per row
+	match filter {
+		Some(filter) => encoder.set_filter(filter),
+		None => encoder.set_adaptive_filter(AdaptiveFilterType::Adaptive),
+	}
+
+	let mut writer = encoder.write_header()?;
+	writer.write_image_data(&reduced.data)?;
+	writer.finish()?;
+
+	Ok(out)
+}
+
+fn optimize(img: &RgbaImage, level: PngOptimization) -> std::io::Result<Vec<u8>> {
+	let reduced = reduce(img);
+
+	match level {
+		PngOptimization::None => unreachable!(),
+
+		// Filtering rarely helps palette images
+		PngOptimization::Fast => {
+			let filter = (reduced.color == ColorType::Indexed).then_some(FilterType::NoFilter);
+			encode(&reduced, img.width(), img.height(), Compression::Default, filter)
+		}
+
+		// Try every filter and keep the smallest result
+		PngOptimization::Max => {
+			let filters = [
+				None,
+				Some(FilterType::NoFilter),
+				Some(FilterType::Sub),
+				Some(FilterType::Up),
+				Some(FilterType::Avg),
+				Some(FilterType::Paeth),
+			];
+			let mut best: Option<Vec<u8>> = None;
+			for filter in filters {
+				let data = encode(&reduced, img.width(), img.height(), Compression::Best, filter)?;
+				if best.as_ref().is_none_or(|x| data.len() < x.len()) {
+					best = Some(data);
+				}
+			}
+			Ok(best.unwrap())
+		}
+	}
+}
+
+/// Write an image as a PNG, optimized losslessly according to the level.
+/// Returns how many bytes were saved compared to the default encoder
+pub fn write_png(img: &RgbaImage, path: &Path, level: PngOptimization) -> std::io::Result<u64> {
+	let mut unoptimized = Vec::new();
+	img.write_to(&mut Cursor::new(&mut unoptimized), ImageFormat::Png)
+		.map_err(std::io::Error::other)?;
+
+	if level == PngOptimization::None {
+		return std::fs::write(path, unoptimized).map(|_| 0);
+	}
+
+	// Never make a file bigger than the default encoder would
+	let optimized = optimize(img, level)?;
+	if optimized.len() < unoptimized.len() {
+		std::fs::write(path, &optimized)?;
+		Ok((unoptimized.len() - optimized.len()) as u64)
+	} else {
+		std::fs::write(path, unoptimized).map(|_| 0)
+	}
+}
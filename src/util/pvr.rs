@@ -0,0 +1,136 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::RgbaImage;
+
+use crate::mod_file::PixelFormat;
+
+// Legacy (v2) PVR pixel formats, which is what cocos2d reads most reliably
+const PVR2_PIXEL_FORMAT_RGBA_4444: u32 = 0x10;
+const PVR2_PIXEL_FORMAT_RGBA_8888: u32 = 0x12;
+const PVR2_FLAG_ALPHA: u32 = 0x8000;
+
+// PVR v3 pixel formats are the channel order followed by the bits of each
+const PVR3_VERSION: u32 = 0x03525650;
+const PVR3_PIXEL_FORMAT_RGBA_4444: u64 = u64::from_le_bytes(*b"rgba\x04\x04\x04\x04");
+const PVR3_PIXEL_FORMAT_RGBA_8888: u64 = u64::from_le_bytes(*b"rgba\x08\x08\x08\x08");
+
+enum PvrPixels {
+	Rgba8888,
+	Rgba4444,
+}
+
+/// Encode an image as a legacy PVR texture
+fn encode_pvr(img: &RgbaImage, format: PixelFormat) -> Vec<u8> {
+	let (pixel_format, bpp, masks, data) = match format {
+		PixelFormat::Rgba8888 => (
+			PVR2_PIXEL_FORMAT_RGBA_8888,
+			32,
+			[0xff000000, 0x00ff0000, 0x0000ff00, 0x000000ff],
+			img.as_raw().clone(),
+		),
+		PixelFormat::Rgba4444 => (
+			PVR2_PIXEL_FORMAT_RGBA_4444,
+			16,
+			[0xf000, 0x0f00, 0x00f0, 0x000f],
+			img.pixels()
+				.flat_map(|p| {
+					let [r, g, b, a] = p.0.map(|x| (x as u16 * 15 + 127) / 255);
+					((r << 12) | (g << 8) | (b << 4) | a).to_le_bytes()
+				})
+				.collect(),
+		),
+	};
+
+	let header = [
+		52, // header length
+		img.height(),
+		img.width(),
+		0, // mipmap count
+		pixel_format | PVR2_FLAG_ALPHA,
+		data.len() as u32,
+		bpp,
+		masks[0],
+		masks[1],
+		masks[2],
+		masks[3],
+		u32::from_le_bytes(*b"PVR!"),
+		1, // surface count
+	];
+
+	let mut out: Vec<u8> = header.iter().flat_map(|x| x.to_le_bytes()).collect();
+	out.extend(data);
+	out
+}
+
+/// Write an image as a zlib-compressed PVR texture with a CCZ header, as
+/// read by cocos2d for `.pvr.ccz` files
+pub fn write_pvr_ccz(img: &RgbaImage, path: &Path, format: PixelFormat) -> std::io::Result<()> {
+	let pvr = encode_pvr(img, format);
+
+	let mut out = Vec::new();
+	out.extend(b"CCZ!");
+	out.extend(0u16.to_be_bytes()); // compression type (zlib)
+	out.extend(2u16.to_be_bytes()); // version
+	out.extend(0u32.to_be_bytes()); // reserved
+	out.extend((pvr.len() as u32).to_be_bytes());
+
+	let mut encoder = ZlibEncoder::new(out, Compression::best());
+	encoder.write_all(&pvr)?;
+	std::fs::write(path, encoder.finish()?)
+}
+
+/// Read an RGBA8888 or RGBA4444 `.pvr.ccz` texture, with either a legacy
+/// header like `write_pvr_ccz` writes or a v3 one like the game's own sheets
+pub fn read_pvr_ccz(path: &Path) -> Result<RgbaImage, String> {
+	let data = std::fs::read(path).map_err(|e| format!("Unable to read {}: {e}", path.display()))?;
+	if data.len() < 16 || &data[0..4] != b"CCZ!" {
+		return Err(format!("{} is not a CCZ file", path.display()));
+	}
+
+	let mut pvr = Vec::new();
+	ZlibDecoder::new(&data[16..])
+		.read_to_end(&mut pvr)
+		.map_err(|e| format!("Unable to decompress {}: {e}", path.display()))?;
+	if pvr.len() < 52 {
+		return Err(format!("{} is not a PVR texture", path.display()));
+	}
+
+	let header = pvr[..52]
+		.chunks(4)
+		.map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+		.collect::<Vec<_>>();
+	let (width, height, format, data) = if header[0] == PVR3_VERSION {
+		let format = match header[2] as u64 | (header[3] as u64) << 32 {
+			PVR3_PIXEL_FORMAT_RGBA_8888 => Some(PvrPixels::Rgba8888),
+			PVR3_PIXEL_FORMAT_RGBA_4444 => Some(PvrPixels::Rgba4444),
+			_ => None,
+		};
+		// Metadata comes between the header and the pixels
+		(header[7], header[6], format, pvr.get(52 + header[12] as usize..).unwrap_or_default())
+	} else {
+		let format = match header[4] & 0xff {
+			PVR2_PIXEL_FORMAT_RGBA_8888 => Some(PvrPixels::Rgba8888),
+			PVR2_PIXEL_FORMAT_RGBA_4444 => Some(PvrPixels::Rgba4444),
+			_ => None,
+		};
+		(header[2], header[1], format, &pvr[52..])
+	};
+	let pixels = width as usize * height as usize;
+
+	let raw = match format {
+		Some(PvrPixels::Rgba8888) if data.len() >= pixels * 4 => data[..pixels * 4].to_vec(),
+		Some(PvrPixels::Rgba4444) if data.len() >= pixels * 2 => data[..pixels * 2]
+			.chunks(2)
+			.flat_map(|x| {
+				let pixel = u16::from_le_bytes([x[0], x[1]]);
+				[12, 8, 4, 0].map(|shift| ((pixel >> shift) & 0xf) as u8 * 17)
+			})
+			.collect(),
+		_ => return Err(format!("Unsupported pixel format in {}", path.display())),
+	};
+	Ok(RgbaImage::from_raw(width, height, raw).unwrap())
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::ImageFormat;
+
+use crate::bmfont::FontBundles;
+use crate::mod_file::ModResources;
+use crate::pvr;
+use crate::spritesheet::{self, SheetBundles};
+use crate::NiceUnwrap;
+
+const SAMPLE_TEXT: &str = "The quick brown fox jumps over the lazy dog 0123456789";
+
+const STYLE: &str = "
+body { font-family: sans-serif; background: #202020; color: #e0e0e0; margin: 2em; }
+h2 { border-bottom: 1px solid #555; padding-bottom: .2em; }
+.tier { margin-bottom: 2em; }
+.atlas { position: relative; display: inline-block; line-height: 0;
+	background: repeating-conic-gradient(#333 0 25%, #444 0 50%) 0 0 / 16px 16px; }
+.frame { position: absolute; box-sizing: border-box; border: 1px solid #ff00ff; }
+.frame:hover { background: rgba(255, 0, 255, .3); }
+.sample { position: relative; background: #333; margin-top: 1em; }
+.glyph { position: absolute; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+td, th { border: 1px solid #555; padding: .2em .6em; text-align: left; }
+.missing { color: #ff8080; }
+";
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+fn file_size(path: &Path) -> String {
+	fs::metadata(path)
+		.map(|x| format!("{:.1} KiB", x.len() as f64 / 1024.0))
+		.unwrap_or_else(|_| "-".into())
+}
+
+/// A built texture, ready to be shown in the report
+struct Texture {
+	/// PNG data URI
+	uri: String,
+	width: u32,
+	height: u32,
+	size: String,
+}
+
+/// Read a texture from its PNG, or from its .pvr.ccz if only that was built
+fn read_texture(png: &Path, pvr: &Path) -> Option<Texture> {
+	if let Ok((width, height)) = image::image_dimensions(png) {
+		return Some(Texture {
+			uri: format!("data:image/png;base64,{}", BASE64.encode(fs::read(png).ok()?)),
+			width,
+			height,
+			size: file_size(png),
+		});
+	}
+
+	let img = pvr::read_pvr_ccz(pvr).ok()?;
+	let mut data = Vec::new();
+	img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png).ok()?;
+	Some(Texture {
+		uri: format!("data:image/png;base64,{}", BASE64.encode(data)),
+		width: img.width(),
+		height: img.height(),
+		size: file_size(pvr),
+	})
+}
+
+struct FntChar {
+	x: i32,
+	y: i32,
+	width: i32,
+	height: i32,
+	xoffset: i32,
+	yoffset: i32,
+	xadvance: i32,
+}
+
+struct Fnt {
+	line_height: i32,
+	chars: HashMap<u32, FntChar>,
+	kernings: HashMap<(u32, u32), i32>,
+}
+
+/// Read the parts of a `.fnt` file needed to lay out text
+fn read_fnt(path: &Path) -> Option<Fnt> {
+	let data = fs::read_to_string(path).ok()?;
+
+	let mut fnt = Fnt {
+		line_height: 0,
+		chars: HashMap::new(),
+		kernings: HashMap::new(),
+	};
+	for line in data.lines() {
+		let mut parts = line.split_whitespace();
+		let tag = parts.next().unwrap_or_default();
+		// Some values, like the space's advance and the line height, are
+		// written as floats
+		let values: HashMap<&str, f32> = parts
+			.filter_map(|x| x.split_once('='))
+			.filter_map(|(k, v)| Some((k, v.parse().ok()?)))
+			.collect();
+		let get = |key: &str| values.get(key).copied().unwrap_or_default().round() as i32;
+
+		match tag {
+			"common" => fnt.line_height = get("lineHeight"),
+			"char" => {
+				fnt.chars.insert(get("id") as u32, FntChar {
+					x: get("x"),
+					y: get("y"),
+					width: get("width"),
+					height: get("height"),
+					xoffset: get("xoffset"),
+					yoffset: get("yoffset"),
+					xadvance: get("xadvance"),
+				});
+			}
+			"kerning" => {
+				fnt.kernings.insert((get("first") as u32, get("second") as u32), get("amount"));
+			}
+			_ => {}
+		}
+	}
+	Some(fnt)
+}
+
+fn write_sheet(html: &mut String, name: &str, bundles: &SheetBundles) {
+	writeln!(html, "<h2>Spritesheet {}</h2>", escape(name)).unwrap();
+
+	for (tier, bundle) in [("UHD", &bundles.uhd), ("HD", &bundles.hd), ("SD", &bundles.sd)] {
+		writeln!(html, "<div class=\"tier\"><h3>{tier}</h3>").unwrap();
+
+		let Ok(plist) = spritesheet::read_sheet_plist(&bundle.plist) else {
+			writeln!(html, "<p class=\"missing\">Unable to read {}</p></div>", bundle.plist.display()).unwrap();
+			continue;
+		};
+		let Some(texture) = read_texture(&bundle.png, &bundle.pvr) else {
+			writeln!(html, "<p class=\"missing\">No texture to preview</p></div>").unwrap();
+			continue;
+		};
+		let (width, height) = (texture.width, texture.height);
+
+		// Rotated frames are stored with their width and height swapped
+		let rects = plist
+			.frames
+			.iter()
+			.map(|frame| {
+				let (w, h) = if frame.rotated {
+					(frame.height, frame.width)
+				} else {
+					(frame.width, frame.height)
+				};
+				(frame, w, h)
+			})
+			.collect::<Vec<_>>();
+		let used: u64 = rects.iter().map(|(_, w, h)| *w as u64 * *h as u64).sum();
+
+		writeln!(
+			html,
+			"<table><tr><th>Size</th><th>Frames</th><th>Occupancy</th><th>File size</th></tr>\
+			<tr><td>{width}x{height}</td><td>{}</td><td>{:.1}%</td><td>{}</td></tr></table>",
+			rects.len(),
+			used as f64 / (width as u64 * height as u64).max(1) as f64 * 100.0,
+			texture.size,
+		)
+		.unwrap();
+
+		writeln!(html, "<div class=\"atlas\"><img src=\"{}\">", texture.uri).unwrap();
+		for (frame, w, h) in rects {
+			writeln!(
+				html,
+				"<div class=\"frame\" title=\"{}\" style=\"left: {}px; top: {}px; width: {w}px; height: {h}px\"></div>",
+				escape(&frame.name), frame.x, frame.y,
+			)
+			.unwrap();
+		}
+		writeln!(html, "</div></div>").unwrap();
+	}
+}
+
+fn write_font(html: &mut String, index: usize, name: &str, bundles: &FontBundles) {
+	writeln!(html, "<h2>Font {}</h2>", escape(name)).unwrap();
+
+	for (tier, bundle) in [("UHD", &bundles.uhd), ("HD", &bundles.hd), ("SD", &bundles.sd)] {
+		writeln!(html, "<div class=\"tier\"><h3>{tier}</h3>").unwrap();
+
+		let Some(fnt) = read_fnt(&bundle.fnt) else {
+			writeln!(html, "<p class=\"missing\">Unable to read {}</p></div>", bundle.fnt.display()).unwrap();
+			continue;
+		};
+		let Some(texture) = read_texture(&bundle.png, &bundle.pvr) else {
+			writeln!(html, "<p class=\"missing\">No texture to preview</p></div>").unwrap();
+			continue;
+		};
+
+		writeln!(
+			html,
+			"<table><tr><th>Characters</th><th>Kerning pairs</th><th>Line height</th><th>File size</th></tr>\
+			<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr></table>",
+			fnt.chars.len(),
+			fnt.kernings.len(),
+			fnt.line_height,
+			texture.size,
+		)
+		.unwrap();
+
+		// The atlas is embedded once as a class shared by the preview and
+		// every glyph of the sample
+		let atlas = format!("font-{index}-{}", tier.to_lowercase());
+		writeln!(
+			html,
+			"<style>.{atlas} {{ background-image: url({}) }}</style>\
+			<div class=\"atlas\"><div class=\"{atlas}\" style=\"width: {}px; height: {}px\"></div></div>",
+			texture.uri, texture.width, texture.height,
+		)
+		.unwrap();
+
+		// Lay out the sample text like cocos2d would, with every glyph
+		// cropped out of the atlas through its background position
+		let mut glyphs = String::new();
+		let mut pen = 0;
+		let mut prev: Option<u32> = None;
+		for c in SAMPLE_TEXT.chars().map(|c| c as u32) {
+			if let Some(prev) = prev {
+				pen += fnt.kernings.get(&(prev, c)).copied().unwrap_or_default();
+			}
+			prev = Some(c);
+
+			let Some(ch) = fnt.chars.get(&c) else { continue };
+			writeln!(
+				glyphs,
+				"<div class=\"glyph {atlas}\" style=\"left: {}px; top: {}px; width: {}px; height: {}px; \
+				background-position: -{}px -{}px\"></div>",
+				pen + ch.xoffset, ch.yoffset, ch.width, ch.height, ch.x, ch.y,
+			)
+			.unwrap();
+			pen += ch.xadvance;
+		}
+		writeln!(
+			html,
+			"<div class=\"sample\" style=\"width: {}px; height: {}px\">{glyphs}</div></div>",
+			pen.max(1), fnt.line_height.max(1),
+		)
+		.unwrap();
+	}
+}
+
+/// Write a self-contained HTML page previewing the spritesheets and fonts
+/// built into the output directory
+pub fn write_report(resources: &ModResources, output_dir: &Path, path: &Path) {
+	let mut html = String::new();
+	writeln!(
+		html,
+		"<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Resource report</title>\
+		<style>{STYLE}</style></head><body><h1>Resource report</h1>"
+	)
+	.unwrap();
+
+	let mut sheets = resources.spritesheets.keys().collect::<Vec<_>>();
+	sheets.sort();
+	for name in sheets {
+		write_sheet(&mut html, name, &SheetBundles::new(output_dir.join(name.to_string() + ".png")));
+	}
+
+	let mut fonts = resources.fonts.keys().collect::<Vec<_>>();
+	fonts.sort();
+	for (index, name) in fonts.into_iter().enumerate() {
+		write_font(&mut html, index, name, &FontBundles::new(output_dir.join(name.to_string() + ".png")));
+	}
+
+	writeln!(html, "</body></html>").unwrap();
+
+	fs::write(path, html).nice_unwrap("Unable to write report");
+}
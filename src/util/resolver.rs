@@ -0,0 +1,172 @@
+use semver::{Version, VersionReq};
+use std::collections::{BTreeMap, HashMap};
+
+/// One version of a mod that could be picked
+pub struct Candidate<T> {
+	/// None for mods whose version isn't known, which satisfy any requirement
+	pub version: Option<Version>,
+	/// What this version depends on in turn, as `(id, requirement)`
+	pub dependencies: Vec<(String, VersionReq)>,
+	/// Where this version comes from
+	pub source: T,
+}
+
+impl<T> Candidate<T> {
+	fn matches(&self, req: &VersionReq) -> bool {
+		self.version.as_ref().map(|x| req.matches(x)).unwrap_or(true)
+	}
+
+	fn describe(&self, id: &str) -> String {
+		match &self.version {
+			Some(version) => format!("'{id}' v{version}"),
+			None => format!("'{id}'"),
+		}
+	}
+}
+
+#[derive(Clone)]
+pub struct Requirement {
+	pub id: String,
+	pub version: VersionReq,
+	/// Who asked for it, like `mod.json` or `'a.b' v1.0.0`
+	pub by: String,
+	/// Whether the mod has to be linked, or is only used if it's there
+	pub required: bool,
+}
+
+pub struct Selected {
+	/// Index of the picked version in the mod's candidates
+	pub candidate: usize,
+	/// Whether anything required needs this mod
+	pub required: bool,
+}
+
+/// Why no set of versions could be picked
+pub struct Conflict {
+	pub id: String,
+	/// Every requirement on the mod, as `(who, requirement)`
+	pub requirements: Vec<(String, VersionReq)>,
+	/// Versions that were found; empty if the mod wasn't found at all
+	pub available: Vec<Option<Version>>,
+}
+
+struct Solver<'a, T> {
+	candidates: &'a HashMap<String, Vec<Candidate<T>>>,
+	/// Last dead end among those reached after picking the most versions,
+	/// which is reported if nothing works as it's usually closest to the
+	/// actual problem
+	conflict: Option<(usize, Conflict)>,
+}
+
+impl<'a, T> Solver<'a, T> {
+	fn candidates_of(&self, id: &str) -> &'a [Candidate<T>] {
+		self.candidates.get(id).map(|x| x.as_slice()).unwrap_or_default()
+	}
+
+	fn record_conflict(&mut self, depth: usize, id: &str, requirements: Vec<&Requirement>) {
+		if self.conflict.as_ref().is_some_and(|(x, _)| *x > depth) {
+			return;
+		}
+		self.conflict = Some((depth, Conflict {
+			id: id.to_string(),
+			requirements: requirements.into_iter().map(|x| (x.by.clone(), x.version.clone())).collect(),
+			available: self.candidates_of(id).iter().map(|x| x.version.clone()).collect(),
+		}));
+	}
+
+	/// Pick a version for the next mod that has requirements but no version
+	/// yet, trying candidates in order and backtracking on conflicts
+	fn solve(&mut self, selected: &mut BTreeMap<String, usize>, requirements: &mut Vec<Requirement>) -> bool {
+		let Some(id) = requirements
+			.iter()
+			.find(|x| !selected.contains_key(&x.id))
+			.map(|x| x.id.clone())
+		else {
+			return true;
+		};
+
+		let mut any_matched = false;
+		for (index, candidate) in self.candidates_of(&id).iter().enumerate() {
+			if !requirements.iter().filter(|x| x.id == id).all(|x| candidate.matches(&x.version)) {
+				continue;
+			}
+			any_matched = true;
+
+			let new = candidate
+				.dependencies
+				.iter()
+				.map(|(dep_id, version)| Requirement {
+					id: dep_id.clone(),
+					version: version.clone(),
+					by: candidate.describe(&id),
+					required: true,
+				})
+				.collect::<Vec<_>>();
+
+			// Its dependencies also have to agree with the versions already picked
+			if let Some(clash) = new.iter().find(|x| {
+				selected
+					.get(&x.id)
+					.is_some_and(|&i| !self.candidates_of(&x.id)[i].matches(&x.version))
+			}) {
+				let on_clash = requirements.iter().filter(|x| x.id == clash.id).chain([clash]).collect();
+				self.record_conflict(selected.len(), &clash.id.clone(), on_clash);
+				continue;
+			}
+
+			let len = requirements.len();
+			requirements.extend(new);
+			selected.insert(id.clone(), index);
+
+			if self.solve(selected, requirements) {
+				return true;
+			}
+
+			selected.remove(&id);
+			requirements.truncate(len);
+		}
+
+		if !any_matched {
+			let on_id = requirements.iter().filter(|x| x.id == id).collect();
+			self.record_conflict(selected.len(), &id, on_id);
+		}
+		false
+	}
+}
+
+/// Pick one version of every mod reachable from the given requirements so
+/// that all requirements, including ones between the picked mods, are
+/// satisfied. Candidates earlier in their list are preferred
+pub fn resolve<T>(
+	candidates: &HashMap<String, Vec<Candidate<T>>>,
+	roots: &[Requirement],
+) -> Result<BTreeMap<String, Selected>, Conflict> {
+	let mut solver = Solver { candidates, conflict: None };
+	let mut selected = BTreeMap::new();
+	let mut requirements = roots.to_vec();
+
+	if !solver.solve(&mut selected, &mut requirements) {
+		return Err(solver.conflict.unwrap().1);
+	}
+
+	// A mod is required if a required root or a required mod depends on it
+	let mut required = roots.iter().filter(|x| x.required).map(|x| x.id.clone()).collect::<Vec<_>>();
+	let mut i = 0;
+	while i < required.len() {
+		let id = required[i].clone();
+		for (dep_id, _) in &candidates[&id][selected[&id]].dependencies {
+			if !required.contains(dep_id) {
+				required.push(dep_id.clone());
+			}
+		}
+		i += 1;
+	}
+
+	Ok(selected
+		.into_iter()
+		.map(|(id, candidate)| {
+			let required = required.contains(&id);
+			(id, Selected { candidate, required })
+		})
+		.collect())
+}
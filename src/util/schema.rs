@@ -0,0 +1,626 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use edit_distance::edit_distance;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::jsonc::{self, Member, Node, NodeKind};
+use crate::version;
+
+const ID_PATTERN: &str = r"^[a-z0-9\-_]+\.[a-z0-9\-_]+$";
+const VERSION_PATTERN: &str =
+	r"^v?(0|[1-9]\d*)\.(0|[1-9]\d*)\.(0|[1-9]\d*)(-[0-9A-Za-z.\-]+)?(\+[0-9A-Za-z.\-]+)?$";
+
+fn glob_list() -> Value {
+	json!({
+		"type": "array",
+		"items": { "type": "string", "format": "glob" },
+	})
+}
+
+fn dependencies_schema() -> Value {
+	json!({
+		"description": "Mods this mod depends on",
+		"type": "array",
+		"items": {
+			"type": "object",
+			"required": ["id", "version"],
+			"additionalProperties": false,
+			"properties": {
+				"id": {
+					"description": "ID of the dependency",
+					"type": "string",
+					"pattern": ID_PATTERN,
+					"patternErrorMessage": "Mod IDs look like 'developer.mod-name', using only lowercase letters, digits, '-' and '_'",
+				},
+				"version": {
+					"description": "Required version of the dependency, like '>=v1.2.0'",
+					"type": "string",
+					"format": "version-req",
+				},
+				"importance": {
+					"description": "Whether the dependency has to be installed",
+					"enum": ["required", "recommended", "suggested"],
+				},
+				"required": {
+					"description": "Deprecated, use 'importance' instead",
+					"type": "boolean",
+				},
+			},
+		},
+	})
+}
+
+fn resources_schema() -> Value {
+	json!({
+		"description": "Resources to package with the mod",
+		"type": "object",
+		"additionalProperties": false,
+		"properties": {
+			"libraries": glob_list(),
+			"files": glob_list(),
+			"shaders": glob_list(),
+			"spritesheets": {
+				"description": "Sprites to pack into a spritesheet, by spritesheet name",
+				"type": "object",
+				"additionalProperties": {
+					"type": "array",
+					"items": { "$ref": "#/definitions/sprite" },
+				},
+			},
+			"sprites": {
+				"type": "array",
+				"items": { "$ref": "#/definitions/sprite" },
+			},
+			"fonts": {
+				"description": "Bitmap fonts to generate, by font name",
+				"type": "object",
+				"additionalProperties": {
+					"type": "object",
+					"required": ["path", "size"],
+					"additionalProperties": false,
+					"properties": {
+						"path": { "type": "string", "format": "path" },
+						"size": { "type": "integer", "minimum": 1 },
+						"charset": { "type": "string" },
+						"outline": { "type": "integer", "minimum": 0 },
+						"color": {
+							"type": "string",
+							"pattern": "^#?([0-9a-fA-F]{3}|[0-9a-fA-F]{6})$",
+							"patternErrorMessage": "Colors look like '#ff8000'",
+						},
+						"faceIndex": { "type": "integer", "minimum": 0 },
+						"instance": {
+							"type": "string",
+							"description": "Named instance of a variable font, like 'Bold'. Kerning uses the default instance's amounts",
+						},
+						"variations": {
+							"type": "object",
+							"description": "Variable font axis values, like { \"weight\": 600 }. Kerning uses the default instance's amounts",
+							"additionalProperties": { "type": "number" },
+						},
+					},
+				},
+			},
+			"textureFormat": {
+				"type": "object",
+				"additionalProperties": false,
+				"properties": {
+					"format": { "enum": ["png", "pvr.ccz", "both"] },
+					"pixelFormat": { "enum": ["rgba8888", "rgba4444"] },
+					"pngOptimization": { "enum": ["none", "fast", "max"] },
+				},
+			},
+			"animationDescriptors": { "type": "boolean" },
+			"localization": {
+				"type": "object",
+				"additionalProperties": false,
+				"properties": {
+					"defaultLanguage": { "type": "string" },
+					"languages": {
+						"description": "Language files (.json, .po or .ftl) by language code",
+						"type": "object",
+						"additionalProperties": { "type": "string", "format": "path" },
+					},
+					"fontCharsets": { "type": "boolean" },
+				},
+			},
+			"minifyShaders": { "type": "boolean" },
+			"sounds": {
+				"type": "object",
+				"additionalProperties": false,
+				"properties": {
+					"files": glob_list(),
+					"transcode": { "type": "boolean" },
+					"quality": { "type": "number", "minimum": -0.2, "maximum": 1.0 },
+					"normalize": { "type": "number", "maximum": 0.0 },
+				},
+			},
+		},
+	})
+}
+
+/// JSON Schema of mod.json, for `project validate` and for editors to
+/// autocomplete with
+pub fn mod_json_schema() -> Value {
+	json!({
+		"$schema": "http://json-schema.org/draft-07/schema#",
+		"title": "mod.json",
+		"description": "Sapphire mod metadata",
+		"type": "object",
+		"required": ["", "id", "name", "version", "developer", "description"],
+		"properties": {
+			"": {
+				"description": "Version of Sapphire the mod targets",
+				"type": "string",
+				"pattern": VERSION_PATTERN,
+				"patternErrorMessage": "Versions look like 'v1.2.0' or 'v1.2.0-beta.1'",
+			},
+			"id": {
+				"description": "Unique ID of the mod, in the form of 'developer.mod-name'",
+				"type": "string",
+				"pattern": ID_PATTERN,
+				"patternErrorMessage": "Mod IDs look like 'developer.mod-name', using only lowercase letters, digits, '-' and '_'",
+			},
+			"name": {
+				"description": "Display name of the mod",
+				"type": "string",
+				"minLength": 1,
+			},
+			"version": {
+				"description": "Version of the mod",
+				"type": "string",
+				"pattern": VERSION_PATTERN,
+				"patternErrorMessage": "Versions look like 'v1.2.0' or 'v1.2.0-beta.1'",
+			},
+			"developer": {
+				"description": "Name of the mod's developer",
+				"type": "string",
+				"minLength": 1,
+			},
+			"description": {
+				"description": "Short description of the mod",
+				"type": "string",
+			},
+			"repository": {
+				"description": "URL of the mod's source code",
+				"type": "string",
+			},
+			"dependencies": dependencies_schema(),
+			"api": {
+				"description": "Headers to expose for other mods to depend on",
+				"type": "object",
+				"required": ["include"],
+				"additionalProperties": false,
+				"properties": {
+					"include": glob_list(),
+				},
+			},
+			"settings": {
+				"description": "Settings users can change, by setting ID",
+				"type": "object",
+				"additionalProperties": {
+					"type": "object",
+					"required": ["type"],
+					"properties": {
+						"type": {
+							"enum": ["bool", "int", "float", "string", "color", "rgba", "file", "custom"],
+						},
+						"name": { "type": "string" },
+						"description": { "type": "string" },
+						"default": {
+							"description": "Default value, required for every type except custom",
+						},
+						"min": {
+							"description": "Smallest value of an int or float setting",
+							"type": "number",
+						},
+						"max": {
+							"description": "Largest value of an int or float setting",
+							"type": "number",
+						},
+						"one-of": {
+							"description": "Allowed values of a string setting",
+							"type": "array",
+							"items": { "type": "string" },
+						},
+					},
+				},
+			},
+			"resources": resources_schema(),
+		},
+		"definitions": {
+			"sprite": {
+				"anyOf": [
+					{ "type": "string", "format": "glob" },
+					{
+						"type": "object",
+						"required": ["path", "size"],
+						"additionalProperties": false,
+						"properties": {
+							"path": { "type": "string", "format": "glob" },
+							"size": {
+								"description": "UHD size to rasterize SVGs at, as [width, height]",
+								"type": "array",
+								"items": { "type": "integer", "minimum": 1 },
+								"minItems": 2,
+								"maxItems": 2,
+							},
+						},
+					},
+				],
+			},
+		},
+	})
+}
+
+#[derive(PartialEq)]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+pub struct Diagnostic {
+	pub line: usize,
+	pub column: usize,
+	pub severity: Severity,
+	pub message: String,
+	pub suggestion: Option<String>,
+}
+
+impl Display for Diagnostic {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "mod.json:{}:{}: {}", self.line, self.column, self.message)
+	}
+}
+
+fn closest<'a>(name: &str, options: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+	options
+		.map(|x| (edit_distance(name, x), x))
+		.filter(|(dist, x)| *dist <= 2 && *dist < x.len())
+		.min_by_key(|(dist, _)| *dist)
+		.map(|(_, x)| x)
+}
+
+struct Validator<'a> {
+	root_schema: &'a Value,
+	root_dir: &'a Path,
+	diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+	fn report(&mut self, node: &Node, path: &str, message: String, suggestion: Option<String>) {
+		self.report_at(node.line, node.column, Severity::Error, path, message, suggestion);
+	}
+
+	fn report_at(
+		&mut self,
+		line: usize,
+		column: usize,
+		severity: Severity,
+		path: &str,
+		message: String,
+		suggestion: Option<String>,
+	) {
+		let path = if path.is_empty() { "[root]" } else { path };
+		self.diagnostics.push(Diagnostic {
+			line,
+			column,
+			severity,
+			message: format!("{path}: {message}"),
+			suggestion,
+		});
+	}
+
+	fn resolve(&self, schema: &'a Value) -> &'a Value {
+		match schema.get("$ref").and_then(|x| x.as_str()) {
+			Some(reference) => {
+				let pointer = reference.trim_start_matches('#');
+				self.root_schema.pointer(pointer).unwrap_or(schema)
+			}
+			None => schema,
+		}
+	}
+
+	fn matches_type(expected: &str, node: &Node) -> bool {
+		match (&node.kind, expected) {
+			(NodeKind::Number(x), "integer") => x.fract() == 0.0,
+			(kind, expected) => kind.type_name() == expected,
+		}
+	}
+
+	fn validate(&mut self, schema: &'a Value, node: &Node, path: &str) {
+		let schema = self.resolve(schema);
+
+		// Only check the branch that could have been meant, so errors are
+		// reported from inside it rather than as a vague mismatch
+		if let Some(branches) = schema.get("anyOf").and_then(|x| x.as_array()) {
+			let branch = branches.iter().map(|x| self.resolve(x)).find(|x| {
+				x.get("type")
+					.and_then(|x| x.as_str())
+					.is_some_and(|x| Self::matches_type(x, node))
+			});
+			match branch {
+				Some(branch) => self.validate(branch, node, path),
+				None => {
+					let types = branches
+						.iter()
+						.filter_map(|x| self.resolve(x).get("type").and_then(|x| x.as_str()))
+						.collect::<Vec<_>>()
+						.join(" or ");
+					self.report(node, path, format!("Expected {types}, found {}", node.kind.type_name()), None);
+				}
+			}
+			return;
+		}
+
+		if let Some(expected) = schema.get("type").and_then(|x| x.as_str()) {
+			if !Self::matches_type(expected, node) {
+				let suggestion = match (&node.kind, expected) {
+					(NodeKind::String(x), "integer" | "number" | "boolean") => {
+						Some(format!("Remove the quotes around \"{x}\""))
+					}
+					(NodeKind::Number(_), "integer") => Some("Use a whole number".into()),
+					_ => None,
+				};
+				self.report(
+					node,
+					path,
+					format!("Expected {expected}, found {}", node.kind.type_name()),
+					suggestion,
+				);
+				return;
+			}
+		}
+
+		if let Some(options) = schema.get("enum").and_then(|x| x.as_array()) {
+			let options = options.iter().filter_map(|x| x.as_str()).collect::<Vec<_>>();
+			let value = match &node.kind {
+				NodeKind::String(x) => Some(x.as_str()),
+				_ => None,
+			};
+			if !value.is_some_and(|x| options.contains(&x)) {
+				let suggestion = value
+					.and_then(|x| closest(x, options.iter().copied()))
+					.map(|x| format!("Did you mean '{x}'?"));
+				self.report(
+					node,
+					path,
+					format!("Expected one of {}", options.iter().map(|x| format!("'{x}'")).collect::<Vec<_>>().join(", ")),
+					suggestion,
+				);
+				return;
+			}
+		}
+
+		match &node.kind {
+			NodeKind::String(value) => self.validate_string(schema, node, path, value),
+			NodeKind::Number(value) => {
+				if let Some(min) = schema.get("minimum").and_then(|x| x.as_f64()) {
+					if *value < min {
+						self.report(node, path, format!("Must be at least {min}"), None);
+					}
+				}
+				if let Some(max) = schema.get("maximum").and_then(|x| x.as_f64()) {
+					if *value > max {
+						self.report(node, path, format!("Must be at most {max}"), None);
+					}
+				}
+			}
+			NodeKind::Array(items) => {
+				let len = items.len() as u64;
+				if let Some(min) = schema.get("minItems").and_then(|x| x.as_u64()) {
+					if len < min {
+						self.report(node, path, format!("Expected at least {min} item(s), found {len}"), None);
+					}
+				}
+				if let Some(max) = schema.get("maxItems").and_then(|x| x.as_u64()) {
+					if len > max {
+						self.report(node, path, format!("Expected at most {max} item(s), found {len}"), None);
+					}
+				}
+				if let Some(item_schema) = schema.get("items") {
+					for (i, item) in items.iter().enumerate() {
+						self.validate(item_schema, item, &format!("{path}[{i}]"));
+					}
+				}
+			}
+			NodeKind::Object(members) => self.validate_object(schema, node, path, members),
+			_ => {}
+		}
+	}
+
+	fn validate_string(&mut self, schema: &'a Value, node: &Node, path: &str, value: &str) {
+		if let Some(min) = schema.get("minLength").and_then(|x| x.as_u64()) {
+			if (value.chars().count() as u64) < min {
+				self.report(node, path, "Must not be empty".into(), None);
+			}
+		}
+
+		if let Some(pattern) = schema.get("pattern").and_then(|x| x.as_str()) {
+			if !Regex::new(pattern).unwrap().is_match(value) {
+				let hint = schema
+					.get("patternErrorMessage")
+					.and_then(|x| x.as_str())
+					.map(|x| x.to_string());
+				self.report(node, path, format!("Invalid value '{value}'"), hint);
+			}
+		}
+
+		match schema.get("format").and_then(|x| x.as_str()) {
+			Some("version-req") => {
+				if let Err(e) = version::parse_version_req(value) {
+					self.report(
+						node,
+						path,
+						e,
+						Some("Requirements look like 'v1.2.0', '>=v1.2.0' or '^v1.2.0'".into()),
+					);
+				}
+			}
+			Some("glob") => {
+				let pattern = self.root_dir.join(value);
+				match glob::glob(pattern.to_str().unwrap()) {
+					Err(e) => self.report(node, path, format!("Invalid glob pattern '{value}': {e}"), None),
+					Ok(mut paths) => {
+						if paths.next().is_none() {
+							self.report_at(
+								node.line,
+								node.column,
+								Severity::Warning,
+								path,
+								format!("'{value}' doesn't match any files"),
+								Some("Paths are relative to the directory of mod.json".into()),
+							);
+						}
+					}
+				}
+			}
+			Some("path") if !self.root_dir.join(value).exists() => {
+				self.report(
+					node,
+					path,
+					format!("File '{value}' doesn't exist"),
+					Some("Paths are relative to the directory of mod.json".into()),
+				);
+			}
+			_ => {}
+		}
+	}
+
+	fn validate_object(
+		&mut self,
+		schema: &'a Value,
+		node: &Node,
+		path: &str,
+		members: &[Member],
+	) {
+		let properties = schema.get("properties").and_then(|x| x.as_object());
+		let additional = schema.get("additionalProperties");
+
+		if let Some(required) = schema.get("required").and_then(|x| x.as_array()) {
+			for key in required.iter().filter_map(|x| x.as_str()) {
+				if !members.iter().any(|x| x.key == key) {
+					let typo = members
+						.iter()
+						.map(|x| x.key.as_str())
+						.find(|name| closest(name, std::iter::once(key)).is_some());
+					let suggestion = match typo {
+						Some(typo) => Some(format!("Rename '{typo}' to '{key}'")),
+						None => Some(format!("Add \"{key}\" to the object")),
+					};
+					self.report(node, path, format!("Missing required key '{key}'"), suggestion);
+				}
+			}
+		}
+
+		for Member { key, line: key_line, column: key_column, value, .. } in members {
+			let member_path = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+
+			if let Some(property) = properties.and_then(|x| x.get(key)) {
+				self.validate(property, value, &member_path);
+				continue;
+			}
+
+			match additional {
+				Some(Value::Bool(false)) => {
+					let suggestion = properties
+						.and_then(|x| closest(key, x.keys().map(|x| x.as_str())))
+						.map(|x| format!("Did you mean '{x}'?"));
+					self.report_at(
+						*key_line,
+						*key_column,
+						Severity::Error,
+						path,
+						format!("Unknown key '{key}'"),
+						suggestion,
+					);
+				}
+				Some(additional @ Value::Object(_)) => self.validate(additional, value, &member_path),
+				// Unknown keys are allowed, but likely typos are still worth pointing out
+				_ => {
+					let close = properties.and_then(|x| closest(key, x.keys().map(|x| x.as_str())));
+					if let Some(close) = close {
+						self.report_at(
+							*key_line,
+							*key_column,
+							Severity::Warning,
+							path,
+							format!("Unknown key '{key}'"),
+							Some(format!("Did you mean '{close}'?")),
+						);
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Position of the value at a path of object keys, like
+/// `["settings", "my-setting"]`, or None if there's no such value
+pub fn position_of(data: &str, path: &[&str]) -> Option<(usize, usize)> {
+	let root = jsonc::parse_spanned(data);
+	let mut node = &root;
+	let mut position = (node.line, node.column);
+	for key in path {
+		let NodeKind::Object(members) = &node.kind else {
+			return None;
+		};
+		let member = members.iter().find(|x| x.key == *key)?;
+		position = (member.line, member.column);
+		node = &member.value;
+	}
+	Some(position)
+}
+
+/// Suggestion for a syntax error reported by serde_json
+fn syntax_suggestion(message: &str) -> Option<String> {
+	if message.contains("trailing comma") {
+		Some("Remove the comma after the last item".into())
+	} else if message.contains("key must be a string") {
+		Some("Keys have to be in double quotes".into())
+	} else if message.contains("expected `,` or") {
+		Some("There's likely a comma missing at the end of the previous line".into())
+	} else if message.contains("EOF while parsing") {
+		Some("There's likely a closing bracket missing".into())
+	} else if message.contains("expected value") {
+		Some("Strings have to be in double quotes".into())
+	} else {
+		None
+	}
+}
+
+/// Check the contents of a mod.json against the schema. Paths in globs are
+/// resolved relative to `root_dir`
+pub fn validate_mod_json(data: &str, root_dir: &Path) -> Vec<Diagnostic> {
+	// Comments and trailing commas are blanked out in place, so positions
+	// are the same as in the original text
+	let data = &jsonc::strip(data);
+	if let Err(e) = serde_json::from_str::<Value>(data) {
+		let message = e.to_string();
+		// serde_json appends the position to the message
+		let message = message.split(" at line ").next().unwrap_or(&message).to_string();
+		return vec![Diagnostic {
+			line: e.line(),
+			column: e.column(),
+			severity: Severity::Error,
+			suggestion: syntax_suggestion(&message),
+			message: format!("Syntax error: {message}"),
+		}];
+	}
+
+	let node = jsonc::parse_spanned(data);
+
+	let schema = mod_json_schema();
+	let mut validator = Validator {
+		root_schema: &schema,
+		root_dir,
+		diagnostics: Vec::new(),
+	};
+	validator.validate(&schema, &node, "");
+
+	let mut diagnostics = validator.diagnostics;
+	diagnostics.sort_by_key(|x| (x.line, x.column));
+	diagnostics
+}
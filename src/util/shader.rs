@@ -0,0 +1,439 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::glsl;
+use crate::{done, fail, fatal, info, NiceUnwrap};
+
+/// Keywords reserved for future use in GLSL ES 1.00, which cocos2d shaders
+/// are compiled as
+const RESERVED_KEYWORDS: &[&str] = &[
+	"asm", "class", "union", "enum", "typedef", "template", "this", "packed",
+	"goto", "switch", "default", "inline", "noinline", "volatile", "public",
+	"static", "extern", "external", "interface", "flat", "long", "short",
+	"double", "half", "fixed", "unsigned", "superp", "input", "output",
+	"hvec2", "hvec3", "hvec4", "dvec2", "dvec3", "dvec4", "fvec2", "fvec3",
+	"fvec4", "sampler1D", "sampler3D", "sampler1DShadow", "sampler2DShadow",
+	"sampler2DRect", "sampler3DRect", "sampler2DRectShadow", "sizeof", "cast",
+	"namespace", "using",
+];
+
+/// Keywords of later GLSL versions that people tend to reach for
+const NEWER_KEYWORDS: &[&str] = &[
+	"layout", "uint", "uvec2", "uvec3", "uvec4", "centroid", "smooth",
+	"sampler2DArray", "isampler2D", "usampler2D", "case",
+];
+
+/// Operators reserved in GLSL ES 1.00
+const RESERVED_OPERATORS: &[&str] = &[
+	"%", "&", "|", "^", "~", "<<", ">>", "%=", "&=", "|=", "^=", "<<=", ">>=",
+];
+
+/// Uniforms cocos2d declares at the top of every shader
+const COCOS_UNIFORMS: &[&str] = &[
+	"CC_PMatrix", "CC_MVMatrix", "CC_MVPMatrix", "CC_Time", "CC_SinTime",
+	"CC_CosTime", "CC_Random01", "CC_Texture0", "CC_Texture1", "CC_Texture2",
+	"CC_Texture3",
+];
+
+/// Longest operators first so they're matched before their prefixes
+const OPERATORS: &[&str] = &[
+	"<<=", ">>=", "++", "--", "<=", ">=", "==", "!=", "&&", "||", "^^", "+=",
+	"-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<", ">>",
+];
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum ShaderStage {
+	Vertex,
+	Fragment,
+}
+
+impl ShaderStage {
+	/// Stage from the file extension, following cocos2d's `.vsh` / `.fsh`
+	pub fn from_path(path: &Path) -> Option<ShaderStage> {
+		match path.extension()?.to_str()? {
+			"vsh" | "vert" => Some(ShaderStage::Vertex),
+			"fsh" | "frag" => Some(ShaderStage::Fragment),
+			_ => None,
+		}
+	}
+}
+
+pub struct ShaderIssue {
+	pub line: usize,
+	pub message: String,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum TokenKind {
+	Ident,
+	Number,
+	Punct,
+}
+
+#[derive(Clone, Copy)]
+pub struct Token<'a> {
+	pub kind: TokenKind,
+	pub text: &'a str,
+	pub line: usize,
+}
+
+pub enum Line<'a> {
+	Directive(&'a str),
+	Code(Vec<Token<'a>>),
+}
+
+/// Replace comments with whitespace, keeping line breaks so line numbers
+/// stay the same
+fn strip_comments(source: &str) -> Result<String, ShaderIssue> {
+	let mut out = String::with_capacity(source.len());
+	let mut chars = source.chars().peekable();
+	let mut line = 1;
+	while let Some(c) = chars.next() {
+		match (c, chars.peek()) {
+			('/', Some('/')) => {
+				while chars.peek().is_some_and(|c| *c != '\n') {
+					chars.next();
+				}
+			}
+			('/', Some('*')) => {
+				let start = line;
+				chars.next();
+				let mut closed = false;
+				while let Some(c) = chars.next() {
+					if c == '\n' {
+						line += 1;
+						out.push('\n');
+					} else if c == '*' && chars.peek() == Some(&'/') {
+						chars.next();
+						closed = true;
+						break;
+					}
+				}
+				if !closed {
+					return Err(ShaderIssue { line: start, message: "Unterminated comment".into() });
+				}
+				out.push(' ');
+			}
+			_ => {
+				if c == '\n' {
+					line += 1;
+				}
+				out.push(c);
+			}
+		}
+	}
+	Ok(out)
+}
+
+pub fn tokenize_line(text: &str, line: usize) -> Result<Vec<Token<'_>>, ShaderIssue> {
+	let mut tokens = Vec::new();
+	let bytes = text.as_bytes();
+	let mut i = 0;
+	while i < bytes.len() {
+		let c = bytes[i] as char;
+		let start = i;
+
+		let kind = if c.is_ascii_whitespace() {
+			i += 1;
+			continue;
+		} else if c.is_ascii_alphabetic() || c == '_' {
+			while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+				i += 1;
+			}
+			TokenKind::Ident
+		} else if c.is_ascii_digit() || (c == '.' && bytes.get(i + 1).is_some_and(|x| x.is_ascii_digit())) {
+			while i < bytes.len() {
+				let b = bytes[i];
+				let exponent_sign = (b == b'+' || b == b'-')
+					&& matches!(bytes[i - 1], b'e' | b'E')
+					&& !text[start..].starts_with("0x");
+				if b.is_ascii_alphanumeric() || b == b'.' || b == b'_' || exponent_sign {
+					i += 1;
+				} else {
+					break;
+				}
+			}
+			TokenKind::Number
+		} else if c.is_ascii_punctuation() {
+			i += OPERATORS
+				.iter()
+				.find(|op| text[i..].starts_with(**op))
+				.map(|op| op.len())
+				.unwrap_or(1);
+			TokenKind::Punct
+		} else {
+			return Err(ShaderIssue { line, message: format!("Unexpected character '{}'", &text[i..].chars().next().unwrap()) });
+		};
+
+		tokens.push(Token { kind, text: &text[start..i], line });
+	}
+	Ok(tokens)
+}
+
+fn split_lines(source: &str) -> Result<Vec<Line<'_>>, ShaderIssue> {
+	source
+		.lines()
+		.enumerate()
+		.map(|(i, text)| {
+			let trimmed = text.trim();
+			if trimmed.starts_with('#') {
+				Ok(Line::Directive(trimmed))
+			} else {
+				tokenize_line(text, i + 1).map(Line::Code)
+			}
+		})
+		.collect()
+}
+
+/// Whether a lowercased number is a valid GLSL ES 1.00 integer or float
+fn is_valid_number(text: &str) -> bool {
+	let digits = |x: &str| !x.is_empty() && x.bytes().all(|b| b.is_ascii_digit());
+	if let Some(hex) = text.strip_prefix("0x") {
+		return !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit());
+	}
+	let (mantissa, exponent) = match text.split_once('e') {
+		Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+		None => (text, None),
+	};
+	if exponent.is_some_and(|x| !digits(x.strip_prefix(['+', '-']).unwrap_or(x))) {
+		return false;
+	}
+	match mantissa.split_once('.') {
+		Some((whole, fraction)) => {
+			(whole.is_empty() || digits(whole))
+				&& (fraction.is_empty() || digits(fraction))
+				&& !(whole.is_empty() && fraction.is_empty())
+		}
+		// Octal integers start with a zero
+		None if exponent.is_none() && mantissa.starts_with('0') => mantissa.bytes().all(|b| (b'0'..=b'7').contains(&b)),
+		None => digits(mantissa),
+	}
+}
+
+fn check_tokens(tokens: &[&Token], stage: ShaderStage, issues: &mut Vec<ShaderIssue>) {
+	let mut issue = |line: usize, message: String| issues.push(ShaderIssue { line, message });
+
+	// Bracket matching
+	let mut stack: Vec<&Token> = Vec::new();
+	// Brace depth, to tell globals from locals
+	let mut depth = 0;
+	for (i, token) in tokens.iter().enumerate() {
+		let next = tokens.get(i + 1).map(|x| x.text);
+
+		match token.kind {
+			TokenKind::Punct => match token.text {
+				"(" | "[" | "{" => {
+					depth += (token.text == "{") as usize;
+					stack.push(token);
+				}
+				")" | "]" | "}" => {
+					let expected = match token.text {
+						")" => "(",
+						"]" => "[",
+						_ => "{",
+					};
+					match stack.pop() {
+						Some(open) if open.text == expected => {}
+						Some(open) => issue(token.line, format!(
+							"'{}' doesn't match '{}' on line {}", token.text, open.text, open.line
+						)),
+						None => issue(token.line, format!("Unmatched '{}'", token.text)),
+					}
+					depth = depth.saturating_sub((token.text == "}") as usize);
+				}
+				op if RESERVED_OPERATORS.contains(&op) => {
+					issue(token.line, format!("Operator '{op}' is reserved in GLSL ES 1.00"));
+				}
+				_ => {}
+			},
+
+			TokenKind::Number => {
+				let text = token.text.to_lowercase();
+				let is_float = !text.starts_with("0x") && (text.contains('.') || text.contains('e'));
+				if text.ends_with('u') {
+					issue(token.line, format!("Unsigned literal '{}' is not supported in GLSL ES 1.00", token.text));
+				} else if is_float && text.ends_with('f') {
+					issue(token.line, format!("Float suffix in '{}' is not supported in GLSL ES 1.00", token.text));
+				} else if !is_valid_number(&text) {
+					issue(token.line, format!("Invalid number '{}'", token.text));
+				}
+			}
+
+			TokenKind::Ident => match token.text {
+				word if RESERVED_KEYWORDS.contains(&word) => {
+					issue(token.line, format!("'{word}' is a reserved keyword in GLSL ES 1.00"));
+				}
+				word if NEWER_KEYWORDS.contains(&word) => {
+					issue(token.line, format!("'{word}' is not available in GLSL ES 1.00"));
+				}
+				"in" | "out" if depth == 0 && stack.is_empty() => {
+					let replacement = match (token.text, stage) {
+						("in", ShaderStage::Vertex) => "attribute",
+						("out", ShaderStage::Fragment) => "gl_FragColor",
+						_ => "varying",
+					};
+					issue(token.line, format!(
+						"Global '{}' is not available in GLSL ES 1.00, use '{replacement}'", token.text
+					));
+				}
+				"texture" | "texelFetch" | "textureLod" if next == Some("(") => {
+					issue(token.line, format!(
+						"'{}' is not available in GLSL ES 1.00, use 'texture2D' or 'textureCube'", token.text
+					));
+				}
+				"attribute" if stage == ShaderStage::Fragment => {
+					issue(token.line, "Fragment shaders can't have attributes".into());
+				}
+				"gl_FragColor" | "gl_FragData" if stage == ShaderStage::Vertex => {
+					issue(token.line, format!("'{}' can't be used in a vertex shader", token.text));
+				}
+				"gl_Position" | "gl_PointSize" if stage == ShaderStage::Fragment => {
+					issue(token.line, format!("'{}' can't be used in a fragment shader", token.text));
+				}
+				// Declaration like `uniform lowp mat4 CC_PMatrix;`
+				word if COCOS_UNIFORMS.contains(&word)
+					&& depth == 0
+					&& tokens[..i].iter().rev().take(3).any(|x| x.text == "uniform") =>
+				{
+					issue(token.line, format!("'{word}' is already declared by cocos2d"));
+				}
+				_ => {}
+			},
+		}
+	}
+
+	for open in stack {
+		issue(open.line, format!("Unclosed '{}'", open.text));
+	}
+
+	let has_main = tokens
+		.windows(3)
+		.any(|x| x[0].text == "void" && x[1].text == "main" && x[2].text == "(");
+	if !has_main {
+		issue(1, "Missing 'void main()'".into());
+	}
+
+	let uses = |name: &str| tokens.iter().any(|x| x.text == name);
+	match stage {
+		ShaderStage::Vertex if !uses("gl_Position") => {
+			issue(1, "Vertex shader never writes 'gl_Position'".into());
+		}
+		ShaderStage::Fragment if !uses("gl_FragColor") && !uses("gl_FragData") => {
+			issue(1, "Fragment shader never writes 'gl_FragColor'".into());
+		}
+		_ => {}
+	}
+}
+
+/// Validate a shader's source as GLSL ES 1.00, which cocos2d compiles shaders
+/// as. The shader is preprocessed and parsed, names are checked to be declared
+/// before they're used, and the mistakes that most often break shaders on
+/// cocos2d are looked for. Types aren't checked, so a shader that passes can
+/// still fail to compile
+pub fn validate_shader(source: &str, stage: ShaderStage) -> Vec<ShaderIssue> {
+	let stripped = match strip_comments(source) {
+		Ok(stripped) => stripped,
+		Err(e) => return vec![e],
+	};
+	let lines = match split_lines(&stripped) {
+		Ok(lines) => lines,
+		Err(e) => return vec![e],
+	};
+
+	let mut issues = Vec::new();
+	let preprocessed = glsl::preprocess(&lines, stage, &mut issues);
+	check_tokens(&preprocessed.code.iter().collect::<Vec<_>>(), stage, &mut issues);
+
+	// The parser mostly trips over the same mistakes again, so only keep what
+	// it finds on lines that are otherwise fine
+	let parse_issues = glsl::parse(&preprocessed.expanded, stage, COCOS_UNIFORMS)
+		.into_iter()
+		.filter(|issue| !issues.iter().any(|x| x.line == issue.line))
+		.collect::<Vec<_>>();
+	issues.extend(parse_issues);
+
+	issues.sort_by_key(|x| x.line);
+	issues
+}
+
+/// Remove comments and unneeded whitespace. Preprocessor directives are kept
+/// on their own lines
+pub fn minify_shader(source: &str) -> String {
+	let stripped = strip_comments(source).unwrap_or_else(|_| source.to_string());
+	let Ok(lines) = split_lines(&stripped) else {
+		return source.to_string();
+	};
+
+	let mut out = String::new();
+	let mut prev: Option<&Token> = None;
+	for line in &lines {
+		match line {
+			Line::Directive(directive) => {
+				if !out.is_empty() && !out.ends_with('\n') {
+					out.push('\n');
+				}
+				out += &directive.split_whitespace().collect::<Vec<_>>().join(" ");
+				out.push('\n');
+				prev = None;
+			}
+			Line::Code(tokens) => {
+				for token in tokens {
+					// Words need to be kept apart, and so do operators that
+					// would merge into another one like `a - -b`
+					let separate = prev.is_some_and(|prev| match (prev.kind, token.kind) {
+						(TokenKind::Punct, TokenKind::Punct) => {
+							let joined = format!("{}{}", prev.text, token.text);
+							OPERATORS.iter().any(|op| op.starts_with(&joined))
+						}
+						(TokenKind::Punct, _) | (_, TokenKind::Punct) => false,
+						_ => true,
+					});
+					if separate {
+						out.push(' ');
+					}
+					out += token.text;
+					prev = Some(token);
+				}
+			}
+		}
+	}
+	if !out.ends_with('\n') {
+		out.push('\n');
+	}
+	out
+}
+
+/// Validate all shaders, then copy them (minified if requested) into the output
+/// directory. Stops the build if any shader has issues
+pub fn build_shaders(shaders: &[PathBuf], minify: bool, output_dir: &Path, shut_up: bool) {
+	if !shut_up {
+		info!("Validating shaders");
+	}
+
+	let mut failed = false;
+	for path in shaders {
+		let Some(stage) = ShaderStage::from_path(path) else {
+			fatal!(
+				"Unknown shader type for '{}', expected a .vsh or .fsh file",
+				path.display()
+			);
+		};
+		let source = fs::read_to_string(path)
+			.nice_unwrap(format!("Unable to read shader '{}'", path.display()));
+
+		let issues = validate_shader(&source, stage);
+		for issue in &issues {
+			fail!("{}:{}: {}", path.display(), issue.line, issue.message);
+		}
+		failed |= !issues.is_empty();
+
+		let output = if minify { minify_shader(&source) } else { source };
+		fs::write(output_dir.join(path.file_name().unwrap()), output)
+			.nice_unwrap(format!("Unable to copy shader '{}'", path.display()));
+	}
+
+	if failed {
+		fatal!("Found problems in shaders");
+	}
+	done!("Validated {} shader(s)", shaders.len());
+}
@@ -1,17 +1,23 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
 
-use image::{imageops, ImageFormat, RgbaImage};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{imageops, AnimationDecoder, Rgba, RgbaImage};
+use resvg::{tiny_skia, usvg};
 use serde_json::json;
 use texture_packer::exporter::ImageExporter;
 use texture_packer::{TexturePacker, TexturePackerConfig};
 use texture_packer::texture::Texture;
 
 use crate::cache::CacheBundle;
+use crate::mod_file::TextureOutput;
+use crate::optimize::write_png;
+use crate::pvr::{read_pvr_ccz, write_pvr_ccz};
 use crate::rgba4444::RGBA4444;
-use crate::{done, info, NiceUnwrap};
-
-use super::mod_file::ModFileInfo;
+use crate::{done, fatal, info, NiceUnwrap};
 
 pub struct Sprite {
 	pub name: String,
@@ -20,19 +26,30 @@ pub struct Sprite {
 
 pub struct SheetBundle {
 	pub png: PathBuf,
+	pub pvr: PathBuf,
 	pub plist: PathBuf,
 }
 
+/// An input file of a sprite or spritesheet
+#[derive(PartialEq, Clone, Debug)]
+pub struct SpriteFile {
+	pub path: PathBuf,
+	/// UHD size to rasterize SVGs at. SVGs use their own size if not given
+	pub size: Option<(u32, u32)>,
+}
+
 #[derive(PartialEq)]
 pub struct SpriteSheet {
 	pub name: String,
-	pub files: Vec<PathBuf>,
+	pub files: Vec<SpriteFile>,
 }
 
 pub struct SheetBundles {
 	pub sd: SheetBundle,
 	pub hd: SheetBundle,
 	pub uhd: SheetBundle,
+	/// Bytes saved by PNG optimization while building, 0 if fetched from cache
+	pub bytes_saved: u64,
 }
 
 impl SheetBundles {
@@ -40,7 +57,10 @@ impl SheetBundles {
 		let mut plist = base.to_owned();
 		plist.set_extension("plist");
 
-		SheetBundle { png: base, plist }
+		let mut pvr = base.to_owned();
+		pvr.set_extension("pvr.ccz");
+
+		SheetBundle { png: base, pvr, plist }
 	}
 
 	pub fn new(mut base: PathBuf) -> SheetBundles {
@@ -55,6 +75,7 @@ impl SheetBundles {
 			sd: SheetBundles::new_file(base),
 			hd: SheetBundles::new_file(hd),
 			uhd: SheetBundles::new_file(uhd),
+			bytes_saved: 0,
 		}
 	}
 
@@ -75,6 +96,15 @@ pub fn read_to_image(path: &Path) -> RgbaImage {
 		.to_rgba8()
 }
 
+/// Read the texture of a cocos spritesheet, which may be a `.pvr.ccz`
+pub fn read_sheet_texture(path: &Path) -> RgbaImage {
+	if path.to_string_lossy().ends_with(".pvr.ccz") {
+		read_pvr_ccz(path).nice_unwrap(format!("Error reading texture '{}'", path.display()))
+	} else {
+		read_to_image(path)
+	}
+}
+
 pub fn downscale(img: &mut RgbaImage, factor: u32) {
 	*img = imageops::resize(
 		img,
@@ -87,26 +117,258 @@ pub fn downscale(img: &mut RgbaImage, factor: u32) {
 	imageops::dither(img, &RGBA4444);
 }
 
-fn initialize_spritesheet_bundle(
-	bundle: &SheetBundle,
-	sheet: &SpriteSheet,
-	factor: u32,
-	mod_info: &ModFileInfo,
-) {
-	// Convert all files to sprites
-	let mut sprites: Vec<Sprite> = sheet
+pub fn is_svg(path: &Path) -> bool {
+	path.extension().is_some_and(|x| x == "svg")
+}
+
+/// Rasterize an SVG at its UHD size divided by the factor
+fn rasterize_svg(path: &Path, size: Option<(u32, u32)>, factor: u32) -> RgbaImage {
+	let data = std::fs::read(path).nice_unwrap(format!("Error reading sprite '{}'", path.display()));
+	let tree = usvg::Tree::from_data(&data, &usvg::Options::default())
+		.nice_unwrap(format!("Error parsing SVG '{}'", path.display()));
+
+	let svg_size = tree.size();
+	let (width, height) = size.unwrap_or((
+		svg_size.width().ceil() as u32,
+		svg_size.height().ceil() as u32,
+	));
+	let (width, height) = ((width / factor).max(1), (height / factor).max(1));
+
+	let mut pixmap = tiny_skia::Pixmap::new(width, height)
+		.nice_unwrap(format!("Invalid size for SVG '{}'", path.display()));
+	resvg::render(
+		&tree,
+		tiny_skia::Transform::from_scale(
+			width as f32 / svg_size.width(),
+			height as f32 / svg_size.height(),
+		),
+		&mut pixmap.as_mut(),
+	);
+
+	// tiny-skia works with premultiplied alpha
+	let mut img = RgbaImage::new(width, height);
+	for (out, pixel) in img.pixels_mut().zip(pixmap.pixels()) {
+		let color = pixel.demultiply();
+		*out = Rgba([color.red(), color.green(), color.blue(), color.alpha()]);
+	}
+	img
+}
+
+/// Read a sprite at the size for the factor. Raster images are downscaled
+/// while SVGs are rasterized directly at that size
+pub fn read_sprite(file: &SpriteFile, factor: u32) -> RgbaImage {
+	if is_svg(&file.path) {
+		let mut img = rasterize_svg(&file.path, file.size, factor);
+		imageops::dither(&mut img, &RGBA4444);
+		img
+	} else {
+		let mut img = read_to_image(&file.path);
+		downscale(&mut img, factor);
+		img
+	}
+}
+
+pub struct AnimationFrame {
+	pub image: RgbaImage,
+	/// Delay before the next frame in seconds
+	pub delay: f32,
+}
+
+/// Decode the frames of an animated GIF or APNG. Returns None for anything
+/// that isn't animated
+pub fn read_animation(path: &Path) -> Option<Vec<AnimationFrame>> {
+	let ext = path.extension()?.to_str()?.to_lowercase();
+	if ext != "gif" && ext != "png" && ext != "apng" {
+		return None;
+	}
+
+	let reader = BufReader::new(
+		File::open(path).nice_unwrap(format!("Error reading sprite '{}'", path.display()))
+	);
+	let frames = if ext == "gif" {
+		GifDecoder::new(reader)
+			.nice_unwrap(format!("Error decoding sprite '{}'", path.display()))
+			.into_frames()
+	} else {
+		let decoder = PngDecoder::new(reader)
+			.nice_unwrap(format!("Error decoding sprite '{}'", path.display()));
+		if !decoder.is_apng() {
+			return None;
+		}
+		decoder.apng().into_frames()
+	};
+
+	let frames = frames
+		.collect_frames()
+		.nice_unwrap(format!("Error decoding animation '{}'", path.display()));
+
+	// Single-frame GIFs are just still images
+	if frames.len() < 2 {
+		return None;
+	}
+
+	Some(
+		frames
+			.into_iter()
+			.map(|frame| {
+				let (numer, denom) = frame.delay().numer_denom_ms();
+				AnimationFrame {
+					delay: numer as f32 / denom as f32 / 1000.0,
+					image: frame.into_buffer(),
+				}
+			})
+			.collect()
+	)
+}
+
+/// Decode every animation among the inputs of a sheet, keyed by path
+pub fn read_animations(sheet: &SpriteSheet) -> HashMap<PathBuf, Vec<AnimationFrame>> {
+	sheet
 		.files
 		.iter()
-		.map(|x| Sprite {
-			name: x.file_stem().unwrap().to_str().unwrap().to_string(),
-			image: read_to_image(x),
-		})
-		.collect();
+		.filter(|x| !is_plist(&x.path))
+		.filter_map(|x| Some((x.path.clone(), read_animation(&x.path)?)))
+		.collect()
+}
+
+pub fn is_plist(path: &Path) -> bool {
+	path.extension().is_some_and(|x| x == "plist")
+}
+
+/// Textures of the prebuilt sheets among the inputs. These are likely matched
+/// by the same glob as their plist, but shouldn't be used as sprites of their
+/// own
+pub fn prebuilt_textures(files: &[SpriteFile]) -> HashSet<PathBuf> {
+	files
+		.iter()
+		.filter(|x| is_plist(&x.path))
+		.filter_map(|x| read_sheet_plist(&x.path).ok())
+		.map(|x| x.texture)
+		.collect()
+}
+
+/// Frames of a prebuilt sheet, cut out of its texture at full size
+pub struct PrebuiltSheet {
+	pub texture: PathBuf,
+	pub frames: Vec<Sprite>,
+}
+
+/// Decode every prebuilt sheet among the inputs of a sheet, keyed by the
+/// path of its plist
+pub fn read_prebuilt_sheets(sheet: &SpriteSheet) -> HashMap<PathBuf, PrebuiltSheet> {
+	let mut sheets = HashMap::new();
+	for file in sheet.files.iter().filter(|x| is_plist(&x.path)) {
+		let prebuilt = read_sheet_plist(&file.path)
+			.nice_unwrap(format!("Unable to read spritesheet '{}'", file.path.display()));
+		let texture = read_sheet_texture(&prebuilt.texture);
+
+		let mut frames = Vec::new();
+		for frame in &prebuilt.frames {
+			let (w, h) = if frame.rotated {
+				(frame.height, frame.width)
+			} else {
+				(frame.width, frame.height)
+			};
+			if frame.x + w > texture.width() || frame.y + h > texture.height() {
+				fatal!(
+					"Frame '{}' in spritesheet '{}' is outside of its texture '{}'",
+					frame.name, file.path.display(), prebuilt.texture.display()
+				);
+			}
+
+			// Frames may already be namespaced like `mod.id/sprite.png`
+			frames.push(Sprite {
+				name: Path::new(&frame.name).file_stem().unwrap().to_str().unwrap().to_string(),
+				image: extract_frame(&texture, frame),
+			});
+		}
+		sheets.insert(file.path.clone(), PrebuiltSheet { texture: prebuilt.texture, frames });
+	}
+	sheets
+}
 
-	// Resize
-	for sprite in &mut sprites {
-		downscale(&mut sprite.image, factor);
+/// Name of a frame of an animation in a sheet, like `name_001`
+pub fn animation_frame_name(name: &str, index: usize) -> String {
+	format!("{}_{:03}", name, index + 1)
+}
+
+/// `mod.id/name`, or just `name` for vanilla names
+fn namespaced(namespace: Option<&str>, name: &str) -> String {
+	match namespace {
+		Some(namespace) => namespace.to_owned() + "/" + name,
+		None => name.to_owned(),
 	}
+}
+
+/// Read all inputs of a sheet into sprites at the size for the factor. Inputs
+/// may be images, SVGs, animations whose frames are split into numbered
+/// sprites, or prebuilt cocos2d spritesheet plists, whose frames are unpacked
+/// into sprites named after the frame
+pub fn read_sheet_sprites(
+	sheet: &SpriteSheet,
+	animations: &HashMap<PathBuf, Vec<AnimationFrame>>,
+	prebuilt: &HashMap<PathBuf, PrebuiltSheet>,
+	factor: u32,
+) -> Vec<Sprite> {
+	let textures = prebuilt.values().map(|x| &x.texture).collect::<HashSet<_>>();
+
+	let mut sprites: Vec<Sprite> = Vec::new();
+	for file in &sheet.files {
+		if textures.contains(&file.path) {
+			continue;
+		}
+		if let Some(prebuilt) = prebuilt.get(&file.path) {
+			for frame in &prebuilt.frames {
+				let mut image = frame.image.clone();
+				downscale(&mut image, factor);
+				sprites.push(Sprite {
+					name: frame.name.clone(),
+					image,
+				});
+			}
+		} else if let Some(frames) = animations.get(&file.path) {
+			let name = file.path.file_stem().unwrap().to_str().unwrap();
+			for (i, frame) in frames.iter().enumerate() {
+				let mut image = frame.image.clone();
+				downscale(&mut image, factor);
+				sprites.push(Sprite {
+					name: animation_frame_name(name, i),
+					image,
+				});
+			}
+		} else {
+			sprites.push(Sprite {
+				name: file.path.file_stem().unwrap().to_str().unwrap().to_string(),
+				image: read_sprite(file, factor),
+			});
+		}
+	}
+
+	// Make sure no two inputs end up with the same name in the sheet
+	let mut names = HashSet::new();
+	for sprite in &sprites {
+		if !names.insert(&sprite.name) {
+			fatal!(
+				"Sprite '{}' is included more than once in spritesheet '{}'",
+				sprite.name, sheet.name
+			);
+		}
+	}
+
+	sprites
+}
+
+fn initialize_spritesheet_bundle(
+	bundle: &SheetBundle,
+	sheet: &SpriteSheet,
+	animations: &HashMap<PathBuf, Vec<AnimationFrame>>,
+	prebuilt: &HashMap<PathBuf, PrebuiltSheet>,
+	factor: u32,
+	namespace: Option<&str>,
+	output: &TextureOutput,
+) -> u64 {
+	// Convert all files to sprites of the right size
+	let sprites = read_sheet_sprites(sheet, animations, prebuilt, factor);
 
 	// Determine maximum dimensions of sprite sheet
 	let largest_width: u32 = sprites.iter().map(|x| x.image.width()).max().unwrap();
@@ -138,12 +400,12 @@ fn initialize_spritesheet_bundle(
 
 	let sprite_name_in_sheet = |name: &String| {
 		// `mod.id/sprite.png`
-		mod_info.id.to_owned()
-			+ "/" + name
-			.strip_suffix("-uhd")
-			.or_else(|| name.strip_suffix("-hd"))
-			.unwrap_or(name)
-			+ ".png"
+		namespaced(
+			namespace,
+			name.strip_suffix("-uhd")
+				.or_else(|| name.strip_suffix("-hd"))
+				.unwrap_or(name)
+		) + ".png"
 	};
 
 	// Initialize the plist file
@@ -167,8 +429,9 @@ fn initialize_spritesheet_bundle(
 	// Using BTreeMap to make sure all packings for the same input produce
 	// identical output via sorted keys
 
-	let texture_file_name = mod_info.id.to_owned()
-		+ "/" + bundle.png.file_name().unwrap().to_str().unwrap();
+	// The plist points to the .pvr.ccz if there is one
+	let texture = if output.pvr() { &bundle.pvr } else { &bundle.png };
+	let texture_file_name = namespaced(namespace, texture.file_name().unwrap().to_str().unwrap());
 
 	// Write plist
 	let plist_file = json!({
@@ -183,15 +446,23 @@ fn initialize_spritesheet_bundle(
 
 	plist::to_file_xml(&bundle.plist, &plist_file).nice_unwrap("Unable to write to plist file");
 
-	// Write png
-	let mut file = std::fs::File::create(&bundle.png).unwrap();
-
 	info!("Exporting");
 
-	let exporter = ImageExporter::export(&texture_packer).unwrap();
-	exporter
-		.write_to(&mut file, ImageFormat::Png)
-		.nice_unwrap("Unable to write to png file");
+	let exporter = ImageExporter::export(&texture_packer).unwrap().to_rgba8();
+
+	// Write png
+	let saved = if output.png() {
+		write_png(&exporter, &bundle.png, output.png_optimization)
+			.nice_unwrap("Unable to write to png file")
+	} else {
+		0
+	};
+
+	// Write pvr.ccz
+	if output.pvr() {
+		write_pvr_ccz(&exporter, &bundle.pvr, output.pixel_format)
+			.nice_unwrap("Unable to write to pvr.ccz file");
+	}
 
 	done!(
 		"Successfully packed {}",
@@ -204,6 +475,8 @@ fn initialize_spritesheet_bundle(
 			.unwrap()
 			.bright_yellow()
 	);
+
+	saved
 }
 
 fn try_extract_from_cache(
@@ -224,25 +497,36 @@ fn try_extract_from_cache(
 
 fn try_extract_bundles_from_cache(
 	sheet: &SpriteSheet,
+	output: &TextureOutput,
+	animation_descriptor: bool,
 	working_dir: &Path,
 	cache: &mut Option<CacheBundle>,
 	shut_up: bool,
 ) -> Option<SheetBundles> {
 	if let Some(cache_bundle) = cache {
 		// Cache found
-		if let Some(p) = cache_bundle.cache.fetch_spritesheet_bundles(sheet) {
+		if let Some(p) = cache_bundle.cache.fetch_spritesheet_bundles(sheet, output, animation_descriptor) {
 			if !shut_up {
 				info!("Using cached files");
 			}
 			let bundles = SheetBundles::new(p.to_path_buf());
+			let descriptor = p.with_file_name(animation_descriptor_name(sheet));
 
 			// Extract all files
-			try_extract_from_cache(&bundles.sd.png, working_dir, cache_bundle, shut_up).then_some(())?;
-			try_extract_from_cache(&bundles.sd.plist, working_dir, cache_bundle, shut_up).then_some(())?;
-			try_extract_from_cache(&bundles.hd.png, working_dir, cache_bundle, shut_up).then_some(())?;
-			try_extract_from_cache(&bundles.hd.plist, working_dir, cache_bundle, shut_up).then_some(())?;
-			try_extract_from_cache(&bundles.uhd.png, working_dir, cache_bundle, shut_up).then_some(())?;
-			try_extract_from_cache(&bundles.uhd.plist, working_dir, cache_bundle, shut_up).then_some(())?;
+			for bundle in [&bundles.sd, &bundles.hd, &bundles.uhd] {
+				if output.png() {
+					try_extract_from_cache(&bundle.png, working_dir, cache_bundle, shut_up).then_some(())?;
+				}
+				if output.pvr() {
+					try_extract_from_cache(&bundle.pvr, working_dir, cache_bundle, shut_up).then_some(())?;
+				}
+				try_extract_from_cache(&bundle.plist, working_dir, cache_bundle, shut_up).then_some(())?;
+			}
+
+			// The cached build only wrote a descriptor if the sheet has animations
+			if animation_descriptor {
+				try_extract_from_cache(&descriptor, working_dir, cache_bundle, shut_up);
+			}
 
 			done!("Fetched {} from cache", sheet.name.bright_yellow());
 			return Some(bundles);
@@ -255,7 +539,9 @@ pub fn get_spritesheet_bundles(
 	sheet: &SpriteSheet,
 	working_dir: &Path,
 	cache: &mut Option<CacheBundle>,
-	mod_info: &ModFileInfo,
+	namespace: Option<&str>,
+	output: &TextureOutput,
+	animation_descriptor: bool,
 	shut_up: bool,
 ) -> SheetBundles {
 	if !shut_up {
@@ -263,7 +549,7 @@ pub fn get_spritesheet_bundles(
 	}
 
 	if let Some(cached) = try_extract_bundles_from_cache(
-		sheet, working_dir, cache, shut_up
+		sheet, output, animation_descriptor, working_dir, cache, shut_up
 	) {
 		return cached;
 	}
@@ -271,19 +557,244 @@ pub fn get_spritesheet_bundles(
 	if !shut_up {
 		info!("Sheet is not cached, building from scratch");
 	}
-	let bundles = SheetBundles::new(working_dir.join(sheet.name.to_string() + ".png"));
+	let mut bundles = SheetBundles::new(working_dir.join(sheet.name.to_string() + ".png"));
+
+	// Decode animations and prebuilt sheets once for all sizes
+	let animations = read_animations(sheet);
+	let prebuilt = read_prebuilt_sheets(sheet);
 
 	// Initialize all files
 
 	info!("Creating normal sheet");
-	initialize_spritesheet_bundle(&bundles.sd, sheet, 4, mod_info);
+	bundles.bytes_saved += initialize_spritesheet_bundle(&bundles.sd, sheet, &animations, &prebuilt, 4, namespace, output);
 
 	info!("Creating HD sheet");
-	initialize_spritesheet_bundle(&bundles.hd, sheet, 2, mod_info);
+	bundles.bytes_saved += initialize_spritesheet_bundle(&bundles.hd, sheet, &animations, &prebuilt, 2, namespace, output);
 
 	info!("Creating UHD sheet");
-	initialize_spritesheet_bundle(&bundles.uhd, sheet, 1, mod_info);
+	bundles.bytes_saved += initialize_spritesheet_bundle(&bundles.uhd, sheet, &animations, &prebuilt, 1, namespace, output);
+
+	if animation_descriptor {
+		write_animation_descriptor(sheet, &animations, working_dir, namespace);
+	}
 
 	done!("Built spritesheet {}", sheet.name.bright_yellow());
 	bundles
 }
+
+fn animation_descriptor_name(sheet: &SpriteSheet) -> String {
+	sheet.name.to_string() + ".animations.json"
+}
+
+/// Write `<sheet>.animations.json` listing the frame names and delays of
+/// every animation in the sheet, so mod code can build a `CCAnimation` from
+/// it. Nothing is written if the sheet has no animations
+fn write_animation_descriptor(
+	sheet: &SpriteSheet,
+	animations: &HashMap<PathBuf, Vec<AnimationFrame>>,
+	output_dir: &Path,
+	namespace: Option<&str>,
+) {
+	let descriptors = animations
+		.iter()
+		.map(|(path, frames)| {
+			let name = path.file_stem().unwrap().to_str().unwrap();
+
+			let frame_names = (0..frames.len())
+				.map(|i| namespaced(namespace, &animation_frame_name(name, i)) + ".png")
+				.collect::<Vec<_>>();
+			let delays = frames.iter().map(|x| x.delay).collect::<Vec<_>>();
+
+			(name.to_string(), json!({ "frames": frame_names, "delays": delays }))
+		})
+		.collect::<BTreeMap<_, _>>();
+
+	if descriptors.is_empty() {
+		return;
+	}
+
+	std::fs::write(
+		output_dir.join(animation_descriptor_name(sheet)),
+		serde_json::to_string_pretty(&json!({ "animations": descriptors })).unwrap(),
+	)
+	.nice_unwrap("Unable to write animation descriptor");
+}
+
+/// A frame read from a cocos2d spritesheet plist
+pub struct SheetFrame {
+	pub name: String,
+	/// Position of the frame in the texture
+	pub x: u32,
+	pub y: u32,
+	/// Size of the trimmed frame, before rotation
+	pub width: u32,
+	pub height: u32,
+	/// Whether the frame is stored rotated 90° clockwise in the texture
+	pub rotated: bool,
+	/// Offset of the trimmed frame's center from the source's center, with
+	/// y pointing up
+	pub offset_x: i32,
+	pub offset_y: i32,
+	/// Size of the sprite before trimming
+	pub source_width: u32,
+	pub source_height: u32,
+}
+
+pub struct SheetPlist {
+	pub frames: Vec<SheetFrame>,
+	pub texture: PathBuf,
+}
+
+/// Parse cocos2d's `{a,b}` and `{{a,b},{c,d}}` value strings
+fn parse_plist_numbers(value: &plist::Value, key: &str) -> Result<Vec<i32>, String> {
+	let text = value
+		.as_string()
+		.ok_or_else(|| format!("Expected '{key}' to be a string"))?;
+	text.replace(['{', '}'], "")
+		.split(',')
+		.map(|x| {
+			x.trim()
+				.parse::<f64>()
+				.map(|x| x.round() as i32)
+				.map_err(|_| format!("Invalid value '{text}' for '{key}'"))
+		})
+		.collect()
+}
+
+fn plist_pair(dict: &plist::Dictionary, key: &str) -> Result<(i32, i32), String> {
+	match parse_plist_numbers(dict.get(key).ok_or_else(|| format!("Missing '{key}'"))?, key)?[..] {
+		[a, b] => Ok((a, b)),
+		_ => Err(format!("Expected '{key}' to be a pair of numbers")),
+	}
+}
+
+fn plist_rect(dict: &plist::Dictionary, key: &str) -> Result<(i32, i32, i32, i32), String> {
+	match parse_plist_numbers(dict.get(key).ok_or_else(|| format!("Missing '{key}'"))?, key)?[..] {
+		[x, y, w, h] => Ok((x, y, w, h)),
+		_ => Err(format!("Expected '{key}' to be a rect")),
+	}
+}
+
+fn plist_number(dict: &plist::Dictionary, key: &str) -> Result<i32, String> {
+	let value = dict.get(key).ok_or_else(|| format!("Missing '{key}'"))?;
+	value
+		.as_real()
+		.or_else(|| value.as_signed_integer().map(|x| x as f64))
+		.map(|x| x.round() as i32)
+		.ok_or_else(|| format!("Expected '{key}' to be a number"))
+}
+
+fn read_sheet_frame(name: &str, dict: &plist::Dictionary, format: i64) -> Result<SheetFrame, String> {
+	let (x, y, width, height, rotated, (offset_x, offset_y), (source_width, source_height)) = match format {
+		0 => (
+			plist_number(dict, "x")?,
+			plist_number(dict, "y")?,
+			plist_number(dict, "width")?,
+			plist_number(dict, "height")?,
+			false,
+			(plist_number(dict, "offsetX")?, plist_number(dict, "offsetY")?),
+			// some exporters write negative original sizes
+			(
+				plist_number(dict, "originalWidth")?.abs(),
+				plist_number(dict, "originalHeight")?.abs(),
+			),
+		),
+		1 | 2 => {
+			let (x, y, w, h) = plist_rect(dict, "frame")?;
+			(
+				x, y, w, h,
+				format == 2 && dict.get("rotated").and_then(|x| x.as_boolean()).unwrap_or(false),
+				plist_pair(dict, "offset")?,
+				plist_pair(dict, "sourceSize")?,
+			)
+		}
+		3 => {
+			let (x, y, _, _) = plist_rect(dict, "textureRect")?;
+			let (w, h) = plist_pair(dict, "spriteSize")?;
+			(
+				x, y, w, h,
+				dict.get("textureRotated").and_then(|x| x.as_boolean()).unwrap_or(false),
+				plist_pair(dict, "spriteOffset")?,
+				plist_pair(dict, "spriteSourceSize")?,
+			)
+		}
+		_ => return Err(format!("Unsupported plist format {format}")),
+	};
+
+	if x < 0 || y < 0 || width < 0 || height < 0 {
+		return Err("Negative frame position or size".into());
+	}
+
+	Ok(SheetFrame {
+		name: name.to_string(),
+		x: x as u32,
+		y: y as u32,
+		width: width as u32,
+		height: height as u32,
+		rotated,
+		offset_x,
+		offset_y,
+		source_width: source_width.max(width) as u32,
+		source_height: source_height.max(height) as u32,
+	})
+}
+
+/// Read the frames of a cocos2d spritesheet plist (formats 0-3) and locate
+/// its texture, which is expected to be next to the plist
+pub fn read_sheet_plist(path: &Path) -> Result<SheetPlist, String> {
+	let value = plist::Value::from_file(path).map_err(|e| format!("Unable to read plist: {e}"))?;
+	let root = value.as_dictionary().ok_or("Expected plist root to be a dictionary")?;
+
+	let metadata = root.get("metadata").and_then(|x| x.as_dictionary());
+	let format = metadata
+		.and_then(|x| x.get("format"))
+		.and_then(|x| x.as_signed_integer())
+		.unwrap_or(0);
+
+	let frames = root
+		.get("frames")
+		.and_then(|x| x.as_dictionary())
+		.ok_or("Missing 'frames' dictionary")?
+		.iter()
+		.map(|(name, frame)| {
+			frame
+				.as_dictionary()
+				.ok_or_else(|| "Expected frame to be a dictionary".to_string())
+				.and_then(|dict| read_sheet_frame(name, dict, format))
+				.map_err(|e| format!("Frame '{name}': {e}"))
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+
+	// The texture file name may include a directory (like our own
+	// `mod.id/sheet.png`), so only the file name is used
+	let texture = metadata
+		.and_then(|x| x.get("textureFileName").or_else(|| x.get("realTextureFileName")))
+		.and_then(|x| x.as_string())
+		.and_then(|x| Path::new(x).file_name())
+		.map(|x| path.with_file_name(x))
+		.filter(|x| x.exists())
+		.unwrap_or_else(|| path.with_extension("png"));
+
+	Ok(SheetPlist { frames, texture })
+}
+
+/// Cut a frame out of its sheet's texture, undoing rotation and trimming
+pub fn extract_frame(texture: &RgbaImage, frame: &SheetFrame) -> RgbaImage {
+	// rotated frames are stored with their width and height swapped
+	let (w, h) = if frame.rotated {
+		(frame.height, frame.width)
+	} else {
+		(frame.width, frame.height)
+	};
+	let mut img = imageops::crop_imm(texture, frame.x, frame.y, w, h).to_image();
+	if frame.rotated {
+		img = imageops::rotate270(&img);
+	}
+
+	// place the trimmed frame back into its original canvas
+	let mut source = RgbaImage::new(frame.source_width, frame.source_height);
+	let left = (frame.source_width as i64 - frame.width as i64) / 2 + frame.offset_x as i64;
+	let top = (frame.source_height as i64 - frame.height as i64) / 2 - frame.offset_y as i64;
+	imageops::replace(&mut source, &img, left, top);
+	source
+}
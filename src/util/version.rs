@@ -0,0 +1,141 @@
+use semver::{Comparator, Op, Prerelease, Version, VersionReq};
+
+/// Remove a single leading `v`, as in `v1.2.0`
+fn strip_v(text: &str) -> &str {
+	text.strip_prefix(['v', 'V']).unwrap_or(text)
+}
+
+/// Parse a version, optionally prefixed with `v` like `v1.2.0-beta.1`
+pub fn parse_version(text: &str) -> Result<Version, String> {
+	Version::parse(strip_v(text.trim())).map_err(|e| format!("Invalid version '{text}': {e}"))
+}
+
+/// Split a single comparator like `>=v1.2.0` into its operator and version,
+/// without the `v` prefix. `==` is accepted as an alias for `=`
+fn normalize_comparator(text: &str) -> Result<String, String> {
+	let version_start = text.find(|c| !matches!(c, '=' | '<' | '>' | '^' | '~')).unwrap_or(text.len());
+	let (op, version) = text.split_at(version_start);
+	let op = if op == "==" { "=" } else { op };
+
+	let version = strip_v(version.trim());
+	if version.is_empty() {
+		return Err(format!("Missing version after '{op}'"));
+	}
+	Ok(format!("{op}{version}"))
+}
+
+/// Parse a version requirement. Comparators may be separated by commas or
+/// spaces (`>=v1.0.0 <v2.0.0`), `a - b` is an inclusive range, and each
+/// version may have a `v` prefix. A bare version means `^version`
+pub fn parse_version_req(text: &str) -> Result<VersionReq, String> {
+	let text = text.trim();
+	if text.is_empty() || text == "*" {
+		return Ok(VersionReq::STAR);
+	}
+
+	let mut comparators = Vec::new();
+	for group in text.split(',') {
+		let mut tokens = group.split_whitespace().peekable();
+		let mut pending_op = String::new();
+
+		while let Some(token) = tokens.next() {
+			// Hyphen ranges, like `v1.0.0 - v2.0.0`
+			if tokens.peek() == Some(&"-") && pending_op.is_empty() {
+				tokens.next();
+				let end = tokens
+					.next()
+					.ok_or_else(|| format!("Missing end of range in '{text}'"))?;
+				comparators.push(normalize_comparator(&format!(">={token}"))?);
+				comparators.push(normalize_comparator(&format!("<={end}"))?);
+				continue;
+			}
+
+			// Operators written apart from their version, like `>= 1.0.0`
+			if token.chars().all(|c| matches!(c, '=' | '<' | '>' | '^' | '~')) {
+				pending_op += token;
+				continue;
+			}
+
+			comparators.push(normalize_comparator(&(std::mem::take(&mut pending_op) + token))?);
+		}
+
+		if !pending_op.is_empty() {
+			return Err(format!("Missing version after '{pending_op}' in '{text}'"));
+		}
+	}
+
+	VersionReq::parse(&comparators.join(", "))
+		.map_err(|e| format!("Invalid version requirement '{text}': {e}"))
+}
+
+/// Requirement matching exactly the given version
+pub fn exact_req(version: &Version) -> VersionReq {
+	VersionReq {
+		comparators: vec![Comparator {
+			op: Op::Exact,
+			major: version.major,
+			minor: Some(version.minor),
+			patch: Some(version.patch),
+			pre: version.pre.clone(),
+		}],
+	}
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum VersionPart {
+	Major,
+	Minor,
+	Patch,
+	/// Next prerelease, like `beta.1` to `beta.2`
+	Pre,
+}
+
+/// Prerelease after the given one, incrementing its last number. Switching
+/// to a different tag starts over at `<tag>.1`
+fn next_prerelease(pre: &Prerelease, tag: Option<&str>) -> Result<Prerelease, String> {
+	let (base, num) = match pre.rsplit_once('.') {
+		Some((base, num)) if num.parse::<u64>().is_ok() => (base, num.parse::<u64>().unwrap()),
+		_ => (pre.as_str(), 0),
+	};
+	let text = match tag {
+		Some(tag) if tag != base => format!("{tag}.1"),
+		_ => format!("{base}.{}", num + 1),
+	};
+	Prerelease::new(&text).map_err(|e| format!("Invalid prerelease '{text}': {e}"))
+}
+
+/// Increase a part of a version and reset the ones after it. A prerelease
+/// of the version a bump would produce is released instead, so
+/// `v1.1.0-beta.2` bumped by minor is `v1.1.0`. Prereleases use the given
+/// tag, or keep the current one, or start at `beta`
+pub fn bump(version: &Version, part: VersionPart, tag: Option<&str>) -> Result<Version, String> {
+	let mut new = Version::new(version.major, version.minor, version.patch);
+	let is_pre = !version.pre.is_empty();
+
+	match part {
+		VersionPart::Major => {
+			if !(is_pre && version.minor == 0 && version.patch == 0) {
+				new = Version::new(version.major + 1, 0, 0);
+			}
+		}
+		VersionPart::Minor => {
+			if !(is_pre && version.patch == 0) {
+				new = Version::new(version.major, version.minor + 1, 0);
+			}
+		}
+		VersionPart::Patch => {
+			if !is_pre {
+				new.patch += 1;
+			}
+		}
+		VersionPart::Pre => {
+			if is_pre {
+				new.pre = next_prerelease(&version.pre, tag)?;
+			} else {
+				new.patch += 1;
+				new.pre = next_prerelease(&Prerelease::EMPTY, Some(tag.unwrap_or("beta")))?;
+			}
+		}
+	}
+	Ok(new)
+}